@@ -7,8 +7,6 @@ use benchmarks::old::SymbolTable as OldSymbolTable;
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
 use fxhash::FxHashMap;
 use hayami::SymbolMap;
-use im;
-use im_rc;
 use indexmap::IndexMap;
 use rand::{thread_rng, Rng};
 use std::borrow::Borrow;
@@ -146,6 +144,19 @@ pub fn exercise_clone_symbol_table<S: SymbolMap<usize, Value = usize> + Clone>(t
     }
 }
 
+pub fn exercise_deep_symbol_table<S: SymbolMap<usize, Value = usize>>(table: &mut S) {
+    for x in 0..200 {
+        table.insert(x, x);
+    }
+    for i in 0..100 {
+        sym_push(table, i..(i + 5), |x| x * i);
+        for x in 0..200 {
+            black_box(table.get(&x));
+        }
+    }
+    table.jump(0);
+}
+
 pub fn layer_benchmarks(c: &mut Criterion) {
     c.bench_function("hayami::SymbolTable: basic usage test", |b| {
         b.iter(|| {
@@ -161,6 +172,14 @@ pub fn layer_benchmarks(c: &mut Criterion) {
             std::mem::drop(table)
         })
     });
+    c.bench_function("hayami_im::SymbolTable (cached): basic usage test", |b| {
+        b.iter(|| {
+            let mut table = hayami_im::SymbolTable::<usize, usize>::default();
+            table.set_cached(true);
+            exercise_symbol_table(&mut table);
+            std::mem::drop(table)
+        })
+    });
     c.bench_function("hayami_im_rc::SymbolTable: basic usage test", |b| {
         b.iter(|| {
             let mut table = hayami_im_rc::SymbolTable::<usize, usize>::default();
@@ -168,6 +187,17 @@ pub fn layer_benchmarks(c: &mut Criterion) {
             std::mem::drop(table)
         })
     });
+    c.bench_function(
+        "hayami_im_rc::SymbolTable (cached): basic usage test",
+        |b| {
+            b.iter(|| {
+                let mut table = hayami_im_rc::SymbolTable::<usize, usize>::default();
+                table.set_cached(true);
+                exercise_symbol_table(&mut table);
+                std::mem::drop(table)
+            })
+        },
+    );
     c.bench_function("Old SymbolTable: basic usage test", |b| {
         b.iter(|| {
             let mut table = OldSymbolTable::<usize, usize>::default();
@@ -189,6 +219,14 @@ pub fn layer_benchmarks(c: &mut Criterion) {
             std::mem::drop(table)
         })
     });
+    c.bench_function("hayami_im::SymbolTable (cached): clone usage test", |b| {
+        b.iter(|| {
+            let mut table = hayami_im::SymbolTable::<usize, usize>::default();
+            table.set_cached(true);
+            exercise_clone_symbol_table(&mut table);
+            std::mem::drop(table)
+        })
+    });
     c.bench_function("hayami_im_rc::SymbolTable: clone usage test", |b| {
         b.iter(|| {
             let mut table = hayami_im_rc::SymbolTable::<usize, usize>::default();
@@ -196,6 +234,17 @@ pub fn layer_benchmarks(c: &mut Criterion) {
             std::mem::drop(table)
         })
     });
+    c.bench_function(
+        "hayami_im_rc::SymbolTable (cached): clone usage test",
+        |b| {
+            b.iter(|| {
+                let mut table = hayami_im_rc::SymbolTable::<usize, usize>::default();
+                table.set_cached(true);
+                exercise_clone_symbol_table(&mut table);
+                std::mem::drop(table)
+            })
+        },
+    );
     c.bench_function("Old SymbolTable: clone usage test", |b| {
         b.iter(|| {
             let mut table = OldSymbolTable::<usize, usize>::default();
@@ -203,6 +252,50 @@ pub fn layer_benchmarks(c: &mut Criterion) {
             std::mem::drop(table)
         })
     });
+    c.bench_function("hayami::SymbolTable: deep usage test", |b| {
+        b.iter(|| {
+            let mut table = hayami::SymbolTable::<usize, usize>::default();
+            exercise_deep_symbol_table(&mut table);
+            std::mem::drop(table)
+        })
+    });
+    c.bench_function("hayami_im::SymbolTable: deep usage test", |b| {
+        b.iter(|| {
+            let mut table = hayami_im::SymbolTable::<usize, usize>::default();
+            exercise_deep_symbol_table(&mut table);
+            std::mem::drop(table)
+        })
+    });
+    c.bench_function("hayami_im::SymbolTable (cached): deep usage test", |b| {
+        b.iter(|| {
+            let mut table = hayami_im::SymbolTable::<usize, usize>::default();
+            table.set_cached(true);
+            exercise_deep_symbol_table(&mut table);
+            std::mem::drop(table)
+        })
+    });
+    c.bench_function("hayami_im_rc::SymbolTable: deep usage test", |b| {
+        b.iter(|| {
+            let mut table = hayami_im_rc::SymbolTable::<usize, usize>::default();
+            exercise_deep_symbol_table(&mut table);
+            std::mem::drop(table)
+        })
+    });
+    c.bench_function("hayami_im_rc::SymbolTable (cached): deep usage test", |b| {
+        b.iter(|| {
+            let mut table = hayami_im_rc::SymbolTable::<usize, usize>::default();
+            table.set_cached(true);
+            exercise_deep_symbol_table(&mut table);
+            std::mem::drop(table)
+        })
+    });
+    c.bench_function("Old SymbolTable: deep usage test", |b| {
+        b.iter(|| {
+            let mut table = OldSymbolTable::<usize, usize>::default();
+            exercise_deep_symbol_table(&mut table);
+            std::mem::drop(table)
+        })
+    });
 }
 
 criterion_group!(benches, layer_benchmarks, insertion_benchmarks);
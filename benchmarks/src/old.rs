@@ -9,6 +9,8 @@ use std::borrow::Borrow;
 use std::default::Default;
 use std::fmt::{self, Debug, Formatter};
 use std::hash::{BuildHasher, Hash};
+use std::iter::{FilterMap, Map, Rev};
+use std::slice;
 
 /**
 A simple, generic symbol table.
@@ -62,8 +64,45 @@ where
     }
 }
 
+/// An iterator over the definitions of a key in a `SymbolTable`, along with their depths
+pub type Defs<'a, V> = Map<Rev<slice::Iter<'a, (V, usize)>>, fn(&'a (V, usize)) -> (&'a V, usize)>;
+
+/// An iterator over the bindings visible in a `SymbolTable`
+pub type Iter<'a, K, V> = FilterMap<
+    indexmap::map::Iter<'a, K, Vec<(V, usize)>>,
+    fn((&'a K, &'a Vec<(V, usize)>)) -> Option<(&'a K, &'a V)>,
+>;
+
+/// An iterator over the bindings defined at the current level of a `SymbolTable`
+#[derive(Debug, Clone)]
+pub struct IterTop<'a, K, V, S> {
+    symbols: &'a IndexMap<K, Vec<(V, usize)>, S>,
+    scope: slice::Iter<'a, usize>,
+}
+
+impl<'a, K, V, S> Iterator for IterTop<'a, K, V, S> {
+    type Item = (&'a K, &'a V);
+    fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        let ix = *self.scope.next()?;
+        let (key, v) = self.symbols.get_index(ix)?;
+        v.last().map(|(value, _)| (key, value))
+    }
+}
+
 impl<K: Hash + Eq, V, S: BuildHasher> SymbolMap<K> for SymbolTable<K, V, S> {
     type Value = V;
+    type Defs<'a>
+        = Defs<'a, V>
+    where
+        Self: 'a;
+    type Iter<'a>
+        = Iter<'a, K, V>
+    where
+        Self: 'a;
+    type IterTop<'a>
+        = IterTop<'a, K, V, S>
+    where
+        Self: 'a;
     #[inline]
     fn get<Q>(&self, key: &Q) -> Option<&V>
     where
@@ -73,11 +112,30 @@ impl<K: Hash + Eq, V, S: BuildHasher> SymbolMap<K> for SymbolTable<K, V, S> {
         self.get_full(key).map(|(v, _)| v)
     }
     #[inline]
+    fn get_full<Q>(&self, key: &Q) -> Option<(&V, usize)>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        SymbolTable::get_full(self, key)
+    }
+    #[inline]
+    fn get_defs<Q>(&self, key: &Q) -> Defs<'_, V>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        SymbolTable::get_defs(self, key)
+            .iter()
+            .rev()
+            .map(|(v, d)| (v, *d))
+    }
+    #[inline]
     fn insert(&mut self, key: K, value: V) {
         let depth = self.depth();
         let entry = self.symbols.entry(key);
         let index = entry.index();
-        let v = entry.or_insert_with(Vec::new);
+        let v = entry.or_default();
         if let Some((old_value, old_depth)) = v.last_mut() {
             if depth == *old_depth {
                 *old_value = value;
@@ -111,8 +169,40 @@ impl<K: Hash + Eq, V, S: BuildHasher> SymbolMap<K> for SymbolTable<K, V, S> {
         self.jump(self.depth().saturating_sub(1))
     }
     #[inline]
+    fn jump(&mut self, depth: usize) {
+        SymbolTable::jump(self, depth)
+    }
+    #[inline]
+    fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        let depth = self.depth();
+        let (ix, _, v) = self.symbols.get_full_mut(key)?;
+        if v.last()?.1 != depth {
+            return None;
+        }
+        let (value, _) = v.pop()?;
+        self.scopes.last_mut().unwrap().retain(|i| *i != ix);
+        Some(value)
+    }
+    #[inline]
+    fn iter(&self) -> Iter<'_, K, V> {
+        self.symbols
+            .iter()
+            .filter_map(|(k, v)| v.last().map(|(v, _)| (k, v)))
+    }
+    #[inline]
+    fn iter_top(&self) -> IterTop<'_, K, V, S> {
+        IterTop {
+            symbols: &self.symbols,
+            scope: self.scopes.last().unwrap().iter(),
+        }
+    }
+    #[inline]
     fn is_empty(&self) -> bool {
-        self.symbols.is_empty()
+        self.symbols.values().all(|v| v.is_empty())
     }
 }
 
@@ -138,7 +228,7 @@ impl<K: Hash + Eq, V, S: BuildHasher> SymbolTable<K, V, S> {
         let depth = self.depth();
         let entry = self.symbols.entry(key);
         let index = entry.index();
-        let v = entry.or_insert_with(Vec::new);
+        let v = entry.or_default();
         if let Some((_, old_depth)) = v.last_mut() {
             if depth == *old_depth {
                 return Err(value);
@@ -179,8 +269,7 @@ impl<K: Hash + Eq, V, S: BuildHasher> SymbolTable<K, V, S> {
     {
         self.symbols
             .get_mut(key)
-            .map(|v| v.last_mut().map(|(v, d)| (v, *d)))
-            .flatten()
+            .and_then(|v| v.last_mut().map(|(v, d)| (v, *d)))
     }
     /// Jump to a given depth, removing obsolete definitions.
     /// Return the number of keys and definitions removed, as well as keys touched, if any.
@@ -0,0 +1,250 @@
+/*!
+Differential fuzzing of the `hayami` symbol tables: arbitrary sequences of operations are run on every implementation
+side by side, which must all agree with the reference model from `symbolmap_trait::testing` on every observable result.
+
+The old 0.1.1 table is also run alongside, through the API it had at the time, along with the removal of definitions
+provided by its `SymbolMap` implementation.
+*/
+#![no_main]
+use arbitrary::Arbitrary;
+use benchmarks::old::SymbolTable as OldSymbolTable;
+use hayami::SymbolMap;
+use libfuzzer_sys::fuzz_target;
+use symbolmap_trait::testing::Model;
+
+/// The number of distinct keys, kept small so that definitions are frequently shadowed
+const KEYS: u8 = 16;
+
+/// The maximum depth jumped to by `Op::Jump`
+const MAX_JUMP: u8 = 8;
+
+/// An operation on a symbol table
+#[derive(Debug, Copy, Clone, Arbitrary)]
+enum Op {
+    Push,
+    Pop,
+    Jump(u8),
+    Insert(u8, u16),
+    TryInsert(u8, u16),
+    Remove(u8),
+    GetMut(u8, u16),
+}
+
+/// The observable state of a symbol table
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Observation {
+    /// The current depth
+    depth: usize,
+    /// Whether the table is empty
+    is_empty: bool,
+    /// The definitions of each key, keeping only the most recent definition at each depth
+    defs: Vec<Vec<(usize, usize)>>,
+    /// The visible bindings, sorted by key
+    visible: Vec<(u8, usize)>,
+    /// The bindings defined at the current level, sorted by key
+    top: Vec<(u8, usize)>,
+}
+
+/// The state of a symbol table observable through the API of the old 0.1.1 table
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct OldObservation {
+    /// The current depth
+    depth: usize,
+    /// The definitions of each key, keeping only the most recent definition at each depth
+    defs: Vec<Vec<(usize, usize)>>,
+}
+
+/// Apply an operation to the reference model, returning the value it reports, if any
+fn apply_model(model: &mut Model, op: Op) -> Option<usize> {
+    match op {
+        Op::Push => model.push(),
+        Op::Pop => model.pop(),
+        Op::Jump(depth) => model.jump((depth % MAX_JUMP) as usize),
+        Op::Insert(key, value) => model.insert(key % KEYS, value as usize),
+        Op::TryInsert(key, value) => return model.try_insert(key % KEYS, value as usize).err(),
+        Op::Remove(key) => return model.remove(key % KEYS),
+        Op::GetMut(key, value) => {
+            return model
+                .get_mut(key % KEYS)
+                .map(|slot| std::mem::replace(slot, value as usize))
+        }
+    }
+    None
+}
+
+/// Apply an operation to a symbol table, returning the value it reports, if any
+fn apply<S: SymbolMap<u8, Value = usize>>(table: &mut S, op: Op) -> Option<usize> {
+    match op {
+        Op::Push => table.push(),
+        Op::Pop => table.pop(),
+        Op::Jump(depth) => table.jump((depth % MAX_JUMP) as usize),
+        Op::Insert(key, value) => table.insert(key % KEYS, value as usize),
+        Op::TryInsert(key, value) => {
+            return table
+                .try_insert(key % KEYS, value as usize)
+                .err()
+                .map(|err| *err.existing)
+        }
+        Op::Remove(key) => return table.remove(&(key % KEYS)),
+        Op::GetMut(key, value) => {
+            return table
+                .try_get_mut(&(key % KEYS))
+                .map(|slot| std::mem::replace(slot, value as usize))
+        }
+    }
+    None
+}
+
+/// Apply an operation to the old 0.1.1 table through its own API, or its `SymbolMap` implementation for removals,
+/// returning the value it reports, if any
+fn apply_old(table: &mut OldSymbolTable<u8, usize>, op: Op) -> Option<usize> {
+    match op {
+        Op::Push => {
+            table.push();
+            None
+        }
+        Op::Pop => {
+            table.pop();
+            None
+        }
+        Op::Jump(depth) => {
+            table.jump((depth % MAX_JUMP) as usize);
+            None
+        }
+        Op::Insert(key, value) => {
+            table.insert(key % KEYS, value as usize);
+            None
+        }
+        Op::TryInsert(key, value) => {
+            let existing = table.get_full(&(key % KEYS)).map(|(value, _)| *value);
+            table
+                .try_insert(key % KEYS, value as usize)
+                .err()
+                .and(existing)
+        }
+        Op::Remove(key) => SymbolMap::remove(table, &(key % KEYS)),
+        Op::GetMut(key, value) => table
+            .get_full_mut(&(key % KEYS))
+            .map(|(slot, _)| std::mem::replace(slot, value as usize)),
+    }
+}
+
+/// Get the definitions of each key in the reference model, keeping only the most recent definition at each depth
+fn model_defs(model: &Model) -> Vec<Vec<(usize, usize)>> {
+    (0..KEYS).map(|key| model.defs(key)).collect()
+}
+
+/// Observe the state of the reference model
+fn observe_model(model: &Model) -> Observation {
+    let mut visible: Vec<_> = model.visible().into_iter().collect();
+    visible.sort_unstable();
+    let mut top: Vec<_> = model
+        .top()
+        .iter()
+        .map(|(key, value)| (*key, *value))
+        .collect();
+    top.sort_unstable();
+    Observation {
+        depth: model.depth(),
+        is_empty: visible.is_empty(),
+        defs: model_defs(model),
+        visible,
+        top,
+    }
+}
+
+/// Observe the state of a symbol table
+fn observe<S: SymbolMap<u8, Value = usize>>(table: &S) -> Observation {
+    let defs = (0..KEYS)
+        .map(|key| {
+            table
+                .get_defs(&key)
+                .map(|(value, depth)| (*value, depth))
+                .collect()
+        })
+        .collect();
+    let mut visible: Vec<_> = table.iter().map(|(key, value)| (*key, *value)).collect();
+    visible.sort_unstable();
+    let mut top: Vec<_> = table
+        .iter_top()
+        .map(|(key, value)| (*key, *value))
+        .collect();
+    top.sort_unstable();
+    Observation {
+        depth: table.depth(),
+        is_empty: table.is_empty(),
+        defs,
+        visible,
+        top,
+    }
+}
+
+/// Observe the state of the old 0.1.1 table through its own API
+fn observe_old(table: &OldSymbolTable<u8, usize>) -> OldObservation {
+    let defs = (0..KEYS)
+        .map(|key| {
+            table
+                .get_defs(&key)
+                .iter()
+                .rev()
+                .map(|(value, depth)| (*value, *depth))
+                .collect()
+        })
+        .collect();
+    OldObservation {
+        depth: table.depth(),
+        defs,
+    }
+}
+
+/// Apply an operation to a symbol table, and check that the results agree with the reference model
+fn check<S: SymbolMap<u8, Value = usize>>(
+    name: &str,
+    table: &mut S,
+    op: Op,
+    expected: &(Option<usize>, Observation),
+) {
+    let result = apply(table, op);
+    let observation = observe(table);
+    assert_eq!(
+        &(result, observation),
+        expected,
+        "{} disagrees with the model after {:?}",
+        name,
+        op
+    );
+}
+
+fuzz_target!(|ops: Vec<Op>| {
+    let mut model = Model::new();
+    let mut hayami = hayami::SymbolTable::<u8, usize>::new();
+    let mut im = hayami_im::SymbolTable::<u8, usize>::new();
+    let mut im_cached = hayami_im::SymbolTable::<u8, usize>::new();
+    im_cached.set_cached(true);
+    let mut im_rc = hayami_im_rc::SymbolTable::<u8, usize>::new();
+    let mut old = OldSymbolTable::<u8, usize>::new();
+    for op in ops {
+        let result = apply_model(&mut model, op);
+        let expected = (result, observe_model(&model));
+        check("hayami", &mut hayami, op, &expected);
+        if let Err(err) = hayami.check_invariants() {
+            panic!("hayami violates an invariant after {:?}: {}", op, err)
+        }
+        check("hayami-im", &mut im, op, &expected);
+        check("hayami-im (cached)", &mut im_cached, op, &expected);
+        check("hayami-im-rc", &mut im_rc, op, &expected);
+        let result = apply_old(&mut old, op);
+        assert_eq!(
+            (result, observe_old(&old)),
+            (
+                expected.0,
+                OldObservation {
+                    depth: model.depth(),
+                    defs: model_defs(&model),
+                }
+            ),
+            "the old 0.1.1 table disagrees with the model after {:?}",
+            op
+        );
+    }
+});
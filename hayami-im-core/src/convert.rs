@@ -0,0 +1,119 @@
+/*!
+Conversions between `SymbolTable`s using different families of pointers, and, with the `hayami` feature, to and from
+`hayami::SymbolTable`. Every conversion keeps the full stack of levels, along with the depth of each definition.
+*/
+use super::*;
+
+impl<K: Hash + Eq + Clone, V: Clone, P: PointerFamily, S: BuildHasher> SymbolTable<K, V, P, S> {
+    /// Get the layers of this table, from the base level up to this one
+    fn layers(&self) -> Vec<&SymbolTable<K, V, P, S>> {
+        let mut layers = Vec::with_capacity(self.depth + 1);
+        let mut layer = Some(self);
+        while let Some(table) = layer {
+            layers.push(table);
+            layer = table.prev.as_deref();
+        }
+        layers.reverse();
+        layers
+    }
+    /// Replay the definitions made at each level of this table, including overloads, onto another symbol table, which
+    /// should be empty
+    fn replay<T: OverloadSymbolMap<K, Value = V>>(&self, target: &mut T) {
+        for layer in self.layers() {
+            target.jump(layer.depth);
+            for (key, value) in layer.symbols.iter() {
+                for earlier in layer.overloads_of(key) {
+                    target.insert_overload(key.clone(), V::clone(earlier))
+                }
+                target.insert_overload(key.clone(), V::clone(value))
+            }
+        }
+        target.jump(self.depth);
+    }
+    /// Copy this table into a table using another family of pointers, keeping every level
+    ///
+    /// The copy is cached if and only if this table is.
+    pub fn to_family<Q: PointerFamily>(&self) -> SymbolTable<K, V, Q, S>
+    where
+        S: Default,
+    {
+        let mut table = SymbolTable::default();
+        self.replay(&mut table);
+        table.set_cached(self.is_cached());
+        table
+    }
+}
+
+#[cfg(feature = "hayami")]
+impl<K, V, P, S> From<&hayami::SymbolTable<K, V, S>> for SymbolTable<K, V, P, S>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+    P: PointerFamily,
+    S: BuildHasher + Default,
+{
+    /// Copy a `hayami::SymbolTable`, with one layer for each of its levels
+    ///
+    /// Where a key is defined more than once at the same level, its earlier definitions are kept as overloads.
+    fn from(table: &hayami::SymbolTable<K, V, S>) -> SymbolTable<K, V, P, S> {
+        let mut levels: Vec<Vec<(&K, &V)>> = (0..=table.depth()).map(|_| Vec::new()).collect();
+        for (key, _) in table.iter() {
+            for (value, depth) in table.get_all_merged(key) {
+                levels[depth].push((key, value));
+            }
+        }
+        let mut result = SymbolTable::default();
+        for (depth, level) in levels.into_iter().enumerate() {
+            result.jump(depth);
+            // Definitions are yielded from most to least recent, so insert them in reverse
+            for (key, value) in level.into_iter().rev() {
+                result.insert_overload(key.clone(), value.clone())
+            }
+        }
+        result
+    }
+}
+
+#[cfg(feature = "hayami")]
+impl<K, V, P, S> From<hayami::SymbolTable<K, V, S>> for SymbolTable<K, V, P, S>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+    P: PointerFamily,
+    S: BuildHasher + Default,
+{
+    #[inline]
+    fn from(table: hayami::SymbolTable<K, V, S>) -> SymbolTable<K, V, P, S> {
+        SymbolTable::from(&table)
+    }
+}
+
+#[cfg(feature = "hayami")]
+impl<K, V, P, S> From<&SymbolTable<K, V, P, S>> for hayami::SymbolTable<K, V, S>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+    P: PointerFamily,
+    S: BuildHasher + Default,
+{
+    /// Copy a `SymbolTable` into a `hayami::SymbolTable`, keeping every level
+    fn from(table: &SymbolTable<K, V, P, S>) -> hayami::SymbolTable<K, V, S> {
+        let mut result = hayami::SymbolTable::default();
+        table.replay(&mut result);
+        result
+    }
+}
+
+#[cfg(feature = "hayami")]
+impl<K, V, P, S> From<SymbolTable<K, V, P, S>> for hayami::SymbolTable<K, V, S>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+    P: PointerFamily,
+    S: BuildHasher + Default,
+{
+    #[inline]
+    fn from(table: SymbolTable<K, V, P, S>) -> hayami::SymbolTable<K, V, S> {
+        hayami::SymbolTable::from(&table)
+    }
+}
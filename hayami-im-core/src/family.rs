@@ -0,0 +1,241 @@
+/*!
+Families of shared pointers, along with the persistent hash maps built on top of them
+*/
+use std::borrow::Borrow;
+use std::hash::{BuildHasher, Hash};
+use std::ops::Deref;
+
+/// A persistent hash map supporting an `O(1)` cloning operation
+pub trait PersistentMap<K, V, S>: Clone {
+    /// A shared pointer to this map's `BuildHasher`
+    type Hasher: Clone + From<S>;
+    /// An iterator over the entries of this map
+    type Iter<'a>: Iterator<Item = (&'a K, &'a V)>
+    where
+        Self: 'a,
+        K: 'a,
+        V: 'a;
+    /// Construct an empty map using the provided hasher
+    fn with_hasher(hasher: Self::Hasher) -> Self;
+    /// Get a reference to this map's hasher
+    fn hasher(&self) -> &Self::Hasher;
+    /// Get the entry associated with a key, if any
+    fn get_key_value<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>;
+    /// Get a mutable reference to the value associated with a key, if any
+    fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>;
+    /// Insert a value, returning the value it replaced, if any
+    fn insert(&mut self, key: K, value: V) -> Option<V>;
+    /// Insert a value, returning a mutable reference to it
+    fn insert_mut(&mut self, key: K, value: V) -> &mut V;
+    /// Remove the entry associated with a key, if any
+    fn remove_with_key<Q>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>;
+    /// Iterate over the entries of this map
+    fn iter(&self) -> Self::Iter<'_>;
+    /// Whether this map is empty
+    fn is_empty(&self) -> bool;
+    /// Get the value associated with a key, if any
+    fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>;
+    /// Whether this map contains a key
+    fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>;
+}
+
+/// A family of shared pointers, along with the persistent hash map built on top of them
+pub trait PointerFamily {
+    /// A shared pointer to a `T`
+    type Pointer<T>: Clone + Deref<Target = T>;
+    /// A shared pointer to a `BuildHasher`, as used by this family's maps
+    type Hasher<S>: Clone + From<S>;
+    /// A persistent hash map built on this family of pointers
+    type Map<K: Hash + Eq + Clone, V: Clone, S: BuildHasher>: PersistentMap<
+        K,
+        V,
+        S,
+        Hasher = Self::Hasher<S>,
+    >;
+    /// Create a new shared pointer
+    fn new<T>(value: T) -> Self::Pointer<T>;
+    /// Get a mutable reference to the value behind a shared pointer, cloning it if it is shared
+    fn make_mut<T: Clone>(this: &mut Self::Pointer<T>) -> &mut T;
+    /// Get the value behind a shared pointer, cloning it if it is shared
+    #[inline]
+    fn unwrap_or_clone<T: Clone>(this: Self::Pointer<T>) -> T {
+        T::clone(&this)
+    }
+}
+
+/// Implement `PersistentMap` for the `HashMap` of an `im` flavour
+#[allow(unused_macros)]
+macro_rules! persistent_map {
+    ($im:ident, $hasher:ty) => {
+        impl<K: Hash + Eq + Clone, V: Clone, S: BuildHasher> PersistentMap<K, V, S>
+            for $im::HashMap<K, V, S>
+        {
+            type Hasher = $hasher;
+            type Iter<'a>
+                = $im::hashmap::Iter<'a, K, V>
+            where
+                Self: 'a,
+                K: 'a,
+                V: 'a;
+            #[inline]
+            fn with_hasher(hasher: Self::Hasher) -> Self {
+                $im::HashMap::with_hasher(hasher)
+            }
+            #[inline]
+            fn hasher(&self) -> &Self::Hasher {
+                $im::HashMap::hasher(self)
+            }
+            #[inline]
+            fn get_key_value<Q>(&self, key: &Q) -> Option<(&K, &V)>
+            where
+                Q: ?Sized + Hash + Eq,
+                K: Borrow<Q>,
+            {
+                $im::HashMap::get_key_value(self, key)
+            }
+            #[inline]
+            fn get<Q>(&self, key: &Q) -> Option<&V>
+            where
+                Q: ?Sized + Hash + Eq,
+                K: Borrow<Q>,
+            {
+                $im::HashMap::get(self, key)
+            }
+            #[inline]
+            fn contains_key<Q>(&self, key: &Q) -> bool
+            where
+                Q: ?Sized + Hash + Eq,
+                K: Borrow<Q>,
+            {
+                $im::HashMap::contains_key(self, key)
+            }
+            #[inline]
+            fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+            where
+                Q: ?Sized + Hash + Eq,
+                K: Borrow<Q>,
+            {
+                $im::HashMap::get_mut(self, key)
+            }
+            #[inline]
+            fn insert(&mut self, key: K, value: V) -> Option<V> {
+                $im::HashMap::insert(self, key, value)
+            }
+            #[inline]
+            fn insert_mut(&mut self, key: K, value: V) -> &mut V {
+                match $im::HashMap::entry(self, key) {
+                    $im::hashmap::Entry::Occupied(entry) => {
+                        let slot = entry.into_mut();
+                        *slot = value;
+                        slot
+                    }
+                    $im::hashmap::Entry::Vacant(entry) => entry.insert(value),
+                }
+            }
+            #[inline]
+            fn remove_with_key<Q>(&mut self, key: &Q) -> Option<(K, V)>
+            where
+                Q: ?Sized + Hash + Eq,
+                K: Borrow<Q>,
+            {
+                $im::HashMap::remove_with_key(self, key)
+            }
+            #[inline]
+            fn iter(&self) -> Self::Iter<'_> {
+                $im::HashMap::iter(self)
+            }
+            #[inline]
+            fn is_empty(&self) -> bool {
+                $im::HashMap::is_empty(self)
+            }
+        }
+    };
+}
+
+#[cfg(feature = "arc")]
+persistent_map!(im, std::sync::Arc<S>);
+#[cfg(feature = "rc")]
+persistent_map!(im_rc, std::rc::Rc<S>);
+
+/// `std::rc::Rc`, along with `im_rc::HashMap`. Faster, but not `Send` + `Sync`.
+#[cfg(feature = "rc")]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub struct RcFamily;
+
+#[cfg(feature = "rc")]
+impl PointerFamily for RcFamily {
+    type Pointer<T> = std::rc::Rc<T>;
+    type Hasher<S> = std::rc::Rc<S>;
+    type Map<K: Hash + Eq + Clone, V: Clone, S: BuildHasher> = im_rc::HashMap<K, V, S>;
+    #[inline]
+    fn new<T>(value: T) -> std::rc::Rc<T> {
+        std::rc::Rc::new(value)
+    }
+    #[inline]
+    fn make_mut<T: Clone>(this: &mut std::rc::Rc<T>) -> &mut T {
+        std::rc::Rc::make_mut(this)
+    }
+    #[inline]
+    fn unwrap_or_clone<T: Clone>(this: std::rc::Rc<T>) -> T {
+        std::rc::Rc::unwrap_or_clone(this)
+    }
+}
+
+/// `std::sync::Arc`, along with `im::HashMap`
+#[cfg(feature = "arc")]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub struct ArcFamily;
+
+#[cfg(feature = "arc")]
+impl PointerFamily for ArcFamily {
+    type Pointer<T> = std::sync::Arc<T>;
+    type Hasher<S> = std::sync::Arc<S>;
+    type Map<K: Hash + Eq + Clone, V: Clone, S: BuildHasher> = im::HashMap<K, V, S>;
+    #[inline]
+    fn new<T>(value: T) -> std::sync::Arc<T> {
+        std::sync::Arc::new(value)
+    }
+    #[inline]
+    fn make_mut<T: Clone>(this: &mut std::sync::Arc<T>) -> &mut T {
+        std::sync::Arc::make_mut(this)
+    }
+    #[inline]
+    fn unwrap_or_clone<T: Clone>(this: std::sync::Arc<T>) -> T {
+        std::sync::Arc::unwrap_or_clone(this)
+    }
+}
+
+/// `elysees::Arc`, along with `im::HashMap`
+#[cfg(feature = "elysees")]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub struct ElyseesFamily;
+
+#[cfg(feature = "elysees")]
+impl PointerFamily for ElyseesFamily {
+    type Pointer<T> = elysees::Arc<T>;
+    type Hasher<S> = std::sync::Arc<S>;
+    type Map<K: Hash + Eq + Clone, V: Clone, S: BuildHasher> = im::HashMap<K, V, S>;
+    #[inline]
+    fn new<T>(value: T) -> elysees::Arc<T> {
+        elysees::Arc::new(value)
+    }
+    #[inline]
+    fn make_mut<T: Clone>(this: &mut elysees::Arc<T>) -> &mut T {
+        elysees::Arc::make_mut(this)
+    }
+}
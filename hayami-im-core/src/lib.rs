@@ -0,0 +1,1406 @@
+/*!
+A generic persistent symbol table implementation supporting `O(1)` `clone`, `push`, and `pop` operations, parameterised
+over a family of shared pointers.
+
+This crate underlies `hayami-im` and `hayami-im-rc`, which provide the table for thread-safe and single-threaded pointer
+families respectively; most users will want one of those crates instead.
+*/
+#![deny(missing_docs, unsafe_code, missing_debug_implementations)]
+
+use ahash::RandomState;
+use std::borrow::Borrow;
+use std::collections::HashSet;
+use std::fmt::{self, Debug, Formatter};
+use std::hash::Hash;
+use std::hash::{BuildHasher, Hasher};
+use std::iter::FromIterator;
+use std::ops::Index;
+
+mod convert;
+mod family;
+#[cfg(feature = "serde")]
+mod serialize;
+
+#[cfg(feature = "arc")]
+pub use family::ArcFamily;
+#[cfg(feature = "elysees")]
+pub use family::ElyseesFamily;
+#[cfg(feature = "rc")]
+pub use family::RcFamily;
+pub use family::{PersistentMap, PointerFamily};
+pub use symbolmap_trait::{
+    AlreadyDefined, DynSymbolMap, Entry, EntrySymbolMap, MutSymbolMap, Namespaced, NamespacedIter,
+    OverloadSymbolMap, PopError, Prelude, SymbolMap, SymbolStack, WithPrelude,
+};
+
+/// A shared pointer to a definition in a `SymbolTable`, which is shared with the caches of the levels it is visible from
+type Shared<V, P> = <P as PointerFamily>::Pointer<V>;
+
+/// The definitions made at a level of a `SymbolTable`
+type Symbols<K, V, P, S> = <P as PointerFamily>::Map<K, Shared<V, P>, S>;
+
+/// A cache of the innermost definition of each visible key in a `SymbolTable`, along with its depth
+type Cache<K, V, P, S> = <P as PointerFamily>::Map<K, (Option<Shared<V, P>>, usize), S>;
+
+/// The earlier definitions of each overloaded key made at a level of a `SymbolTable`, from least to most recent
+type Overloaded<K, V, P, S> = <P as PointerFamily>::Map<K, Vec<Shared<V, P>>, S>;
+
+/// A shared pointer to a layer of a `SymbolTable`
+type Layer<K, V, P, S, M> = <P as PointerFamily>::Pointer<SymbolTable<K, V, P, S, M>>;
+
+/**
+A symbol table implementation supporting snapshots, i.e. an `O(1)` cloning operation.
+
+Each level of the table stores only the definitions made at that level, with lookups of outer definitions
+walking the chain of levels. Optionally, each level may also carry a cache of every visible definition,
+which makes lookups independent of the number of levels; see [`SymbolTable::set_cached`].
+
+Each level also carries a piece of metadata of type `M`, which is pushed along with it by [`SymbolTable::push_with`]
+and returned when it is popped by [`SymbolTable::pop_with`].
+*/
+pub struct SymbolTable<
+    K: Hash + Eq + Clone,
+    V: Clone,
+    P: PointerFamily,
+    S: BuildHasher = RandomState,
+    M = (),
+> {
+    /// The definitions made at this level of the symbol table
+    symbols: Symbols<K, V, P, S>,
+    /// The earlier definitions made at this level of each key overloaded via `insert_overload`, if any key is
+    overloads: Option<Overloaded<K, V, P, S>>,
+    /// A cache of the innermost definition of each visible key and its depth, if enabled
+    ///
+    /// Definitions are shared with the level they were made at rather than copied. Definitions which have been mutated
+    /// since they were cached are looked up in the level they were made at instead, so that they need not be shared.
+    cache: Option<Cache<K, V, P, S>>,
+    /// The depth of this symbol table
+    depth: usize,
+    /// A link to the previous layer's table, forming a singly-linked list
+    prev: Option<Layer<K, V, P, S, M>>,
+    /// The metadata carried by this level
+    metadata: M,
+}
+
+impl<K: Hash + Eq + Clone, V: Clone, P: PointerFamily, S: BuildHasher + Default, M: Default> Default
+    for SymbolTable<K, V, P, S, M>
+{
+    #[inline]
+    fn default() -> SymbolTable<K, V, P, S, M> {
+        SymbolTable::with_metadata(M::default())
+    }
+}
+
+impl<K: Hash + Eq + Clone, V: Clone, P: PointerFamily, S: BuildHasher + Default, M>
+    SymbolTable<K, V, P, S, M>
+{
+    /// Create a new symbol table whose base level carries the given metadata
+    #[inline]
+    pub fn with_metadata(metadata: M) -> SymbolTable<K, V, P, S, M> {
+        SymbolTable {
+            symbols: P::Map::with_hasher(S::default().into()),
+            overloads: None,
+            cache: None,
+            depth: 0,
+            prev: None,
+            metadata,
+        }
+    }
+}
+
+impl<K: Hash + Eq + Clone, V: Clone, P: PointerFamily> SymbolTable<K, V, P> {
+    /// Create a new, empty symbol table
+    #[inline]
+    pub fn new() -> SymbolTable<K, V, P> {
+        Self::default()
+    }
+}
+
+impl<K: Hash + Eq + Clone, V: Clone, P: PointerFamily, S: BuildHasher> SymbolTable<K, V, P, S> {
+    /// Construct an empty hash map using the provided hasher.
+    #[inline]
+    pub fn with_hasher<RS>(hasher: RS) -> SymbolTable<K, V, P, S>
+    where
+        P::Hasher<S>: From<RS>,
+    {
+        SymbolTable {
+            symbols: P::Map::with_hasher(hasher.into()),
+            overloads: None,
+            cache: None,
+            depth: 0,
+            prev: None,
+            metadata: (),
+        }
+    }
+}
+
+impl<K: Hash + Eq + Clone, V: Clone, P: PointerFamily, S: BuildHasher, M>
+    SymbolTable<K, V, P, S, M>
+{
+    /// Get a reference to the table's BuildHasher
+    #[inline]
+    pub fn hasher(&self) -> &P::Hasher<S> {
+        self.symbols.hasher()
+    }
+    /// Whether this level of the table caches the depth of each visible definition
+    #[inline]
+    pub fn is_cached(&self) -> bool {
+        self.cache.is_some()
+    }
+    /// Get the layer of this table at a given depth, if any
+    #[inline]
+    fn layer(&self, depth: usize) -> Option<&SymbolTable<K, V, P, S, M>> {
+        let mut table = self;
+        while table.depth > depth {
+            table = table.prev.as_deref()?;
+        }
+        Some(table)
+    }
+    /// Look up the innermost definition of a key, along with its depth
+    #[inline]
+    fn lookup<Q>(&self, key: &Q) -> Option<(&K, &Shared<V, P>, usize)>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        if let Some(cache) = &self.cache {
+            let (cached, (value, depth)) = cache.get_key_value(key)?;
+            if let Some(value) = value {
+                return Some((cached, value, *depth));
+            }
+            let table = self.layer(*depth)?;
+            let (key, value) = table.symbols.get_key_value(key)?;
+            return Some((key, value, table.depth));
+        }
+        let mut table = Some(self);
+        while let Some(layer) = table {
+            if let Some((key, value)) = layer.symbols.get_key_value(key) {
+                return Some((key, value, layer.depth));
+            }
+            table = layer.prev.as_deref();
+        }
+        None
+    }
+    /// Get the earlier definitions of a key made at this level, from least to most recent
+    #[inline]
+    fn overloads_of<Q>(&self, key: &Q) -> &[Shared<V, P>]
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        self.overloads
+            .as_ref()
+            .and_then(|overloads| overloads.get(key))
+            .map_or(&[][..], |earlier| &earlier[..])
+    }
+    /// Discard the earlier definitions of a key made at this level
+    #[inline]
+    fn clear_overloads<Q>(&mut self, key: &Q)
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        if let Some(overloads) = &mut self.overloads {
+            overloads.remove_with_key(key);
+            if overloads.is_empty() {
+                self.overloads = None;
+            }
+        }
+    }
+    /// Define a key at the current level, returning any definition of it previously made at this level
+    #[inline]
+    fn insert_latest(&mut self, key: K, value: V) -> Option<Shared<V, P>> {
+        let value = P::new(value);
+        if let Some(cache) = &mut self.cache {
+            cache.insert(key.clone(), (Some(value.clone()), self.depth));
+        }
+        self.symbols.insert(key, value)
+    }
+    /// Define a key at the current level, keeping any definition of it previously made at this level as an overload
+    #[inline]
+    fn push_overload(&mut self, key: K, value: V) {
+        if let Some(previous) = self.insert_latest(key.clone(), value) {
+            let hasher = self.symbols.hasher();
+            let overloads = self
+                .overloads
+                .get_or_insert_with(|| P::Map::with_hasher(hasher.clone()));
+            match overloads.get_mut(&key) {
+                Some(earlier) => earlier.push(previous),
+                None => {
+                    overloads.insert(key, vec![previous]);
+                }
+            }
+        }
+    }
+    /// Iterate over the overloads of a key at the level of its innermost definition and, if `merged`, every enclosing
+    /// level
+    #[inline]
+    fn overloads<Q>(&self, key: &Q, merged: bool) -> Overloads<'_, K, V, P, S, M>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        match self.lookup(key) {
+            Some((key, _, depth)) => Overloads {
+                key: Some(key),
+                earlier: &[],
+                depth,
+                table: self.layer(depth),
+                merged,
+            },
+            None => Overloads {
+                key: None,
+                earlier: &[],
+                depth: 0,
+                table: None,
+                merged,
+            },
+        }
+    }
+}
+
+impl<K: Hash + Eq + Clone, V: Clone, P: PointerFamily, S: BuildHasher, M: PartialEq> PartialEq
+    for SymbolTable<K, V, P, S, M>
+where
+    Symbols<K, V, P, S>: PartialEq,
+    Overloaded<K, V, P, S>: PartialEq,
+{
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        //TODO: think about comparing previous tables...
+        self.depth == other.depth
+            && self.symbols == other.symbols
+            && self.overloads == other.overloads
+            && self.metadata == other.metadata
+            && self.prev.as_deref() == other.prev.as_deref()
+    }
+}
+
+impl<K: Hash + Eq + Clone, V: Clone, P: PointerFamily, S: BuildHasher, M: Eq> Eq
+    for SymbolTable<K, V, P, S, M>
+where
+    Symbols<K, V, P, S>: Eq,
+    Overloaded<K, V, P, S>: Eq,
+{
+}
+
+impl<K: Hash + Eq + Clone, V: Clone, P: PointerFamily, S: BuildHasher, M: Hash> Hash
+    for SymbolTable<K, V, P, S, M>
+where
+    Symbols<K, V, P, S>: Hash,
+    Overloaded<K, V, P, S>: Hash,
+{
+    fn hash<H: Hasher>(&self, hasher: &mut H) {
+        //TODO: think about hashing previous tables...
+        self.symbols.hash(hasher);
+        self.overloads.hash(hasher);
+        self.depth.hash(hasher);
+        self.metadata.hash(hasher);
+        self.prev.as_deref().hash(hasher);
+    }
+}
+
+impl<K: Hash + Eq + Clone, V: Clone, P: PointerFamily, S: BuildHasher, M: Debug> Debug
+    for SymbolTable<K, V, P, S, M>
+where
+    Symbols<K, V, P, S>: Debug,
+    Overloaded<K, V, P, S>: Debug,
+{
+    #[inline]
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        fmt.debug_struct("SymbolTable")
+            .field("symbols", &self.symbols)
+            .field("overloads", &self.overloads)
+            .field("cached", &self.is_cached())
+            .field("depth", &self.depth)
+            .field("metadata", &self.metadata)
+            .field("prev", &self.prev.as_deref())
+            .finish()
+    }
+}
+
+impl<K: Hash + Eq + Clone, V: Clone, P: PointerFamily, S: BuildHasher, M>
+    SymbolTable<K, V, P, S, M>
+{
+    /// Get a pointer to the previous layer's table, if there is any
+    #[inline]
+    pub fn get_prev(&self) -> Option<&Layer<K, V, P, S, M>> {
+        self.prev.as_ref()
+    }
+}
+
+impl<K: Hash + Eq + Clone, V: Clone, P: PointerFamily, S: BuildHasher, M: Clone> Clone
+    for SymbolTable<K, V, P, S, M>
+{
+    #[inline]
+    fn clone(&self) -> SymbolTable<K, V, P, S, M> {
+        SymbolTable {
+            symbols: self.symbols.clone(),
+            overloads: self.overloads.clone(),
+            cache: self.cache.clone(),
+            depth: self.depth,
+            prev: self.prev.clone(),
+            metadata: self.metadata.clone(),
+        }
+    }
+}
+
+impl<K: Hash + Eq + Clone, V: Clone, P: PointerFamily, S: BuildHasher, M: Clone>
+    SymbolTable<K, V, P, S, M>
+{
+    /// Get a new symbol table extending this one
+    #[inline]
+    pub fn extended(mut self) -> SymbolTable<K, V, P, S, M>
+    where
+        M: Default,
+    {
+        self.push();
+        self
+    }
+    /// Get a new symbol table extending this one
+    ///
+    /// Since this takes precedence over `Extend::extend` in method calls, extending a table with bindings requires
+    /// calling `Extend::extend` explicitly while this alias remains.
+    #[deprecated(note = "renamed to `extended`, as it clashes with `Extend::extend`")]
+    #[inline]
+    pub fn extend(self) -> SymbolTable<K, V, P, S, M>
+    where
+        M: Default,
+    {
+        self.extended()
+    }
+    /// Push a level carrying the given metadata onto this symbol table
+    #[inline]
+    pub fn push_with(&mut self, metadata: M) {
+        let layer = SymbolTable {
+            symbols: P::Map::with_hasher(self.symbols.hasher().clone()),
+            overloads: None,
+            cache: self.cache.clone(),
+            depth: self.depth + 1,
+            prev: None,
+            metadata,
+        };
+        let prev = std::mem::replace(self, layer);
+        self.prev = Some(P::new(prev));
+    }
+    /// Pop a level from this symbol table, returning its metadata, or failing if it is at depth 0
+    #[inline]
+    pub fn pop_with(&mut self) -> Result<M, PopError> {
+        let prev = self.prev.take().ok_or(PopError)?;
+        let top = std::mem::replace(self, (*prev).clone());
+        Ok(top.metadata)
+    }
+    /// Get the metadata of the current level
+    #[inline]
+    pub fn metadata(&self) -> &M {
+        &self.metadata
+    }
+    /// Get the metadata of the current level, mutably
+    #[inline]
+    pub fn metadata_mut(&mut self) -> &mut M {
+        &mut self.metadata
+    }
+    /// Get the metadata of the level at a given depth, if any
+    #[inline]
+    pub fn metadata_at(&self, depth: usize) -> Option<&M> {
+        self.layer(depth)
+            .filter(|table| table.depth == depth)
+            .map(|table| &table.metadata)
+    }
+    /// Iterate over the metadata of the current level and each enclosing level, from the innermost out
+    #[inline]
+    pub fn enclosing_metadata(&self) -> EnclosingMetadata<'_, K, V, P, S, M> {
+        EnclosingMetadata(Some(self))
+    }
+    /// Enable or disable the lookup cache for this level and any levels pushed on top of it
+    ///
+    /// Enabling the cache takes time linear in the number of definitions in the table. Afterwards, lookups no
+    /// longer walk the chain of levels, at the cost of updating the cache on insertion. Definitions are shared with the
+    /// cache rather than copied into it.
+    pub fn set_cached(&mut self, cached: bool) {
+        if !cached {
+            self.cache = None;
+            return;
+        }
+        if self.cache.is_some() {
+            return;
+        }
+        let mut cache = P::Map::with_hasher(self.symbols.hasher().clone());
+        let mut table = Some(&*self);
+        while let Some(layer) = table {
+            for (key, value) in layer.symbols.iter() {
+                if !cache.contains_key(key) {
+                    cache.insert(key.clone(), (Some(value.clone()), layer.depth));
+                }
+            }
+            table = layer.prev.as_deref();
+        }
+        self.cache = Some(cache);
+    }
+    /// Get a mutable reference to the definition of a key at a given depth, if any
+    ///
+    /// Any layers shared with other tables along the way are copied, so that mutations are not visible to them.
+    #[inline]
+    fn get_mut_at<Q>(&mut self, key: &Q, depth: usize) -> Option<&mut V>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        let mut table = self;
+        loop {
+            if let Some((value, _)) = table.cache.as_mut().and_then(|cache| cache.get_mut(key)) {
+                *value = None;
+            }
+            if table.depth <= depth {
+                return table.symbols.get_mut(key).map(P::make_mut);
+            }
+            table = P::make_mut(table.prev.as_mut()?);
+        }
+    }
+    /// Define a key at the current level, returning a mutable reference to its new value
+    #[inline]
+    fn define(&mut self, key: K, value: V) -> &mut V {
+        self.clear_overloads(&key);
+        if let Some(cache) = &mut self.cache {
+            cache.insert(key.clone(), (None, self.depth));
+        }
+        P::make_mut(self.symbols.insert_mut(key, P::new(value)))
+    }
+}
+
+/// An iterator over the metadata of the levels of a `SymbolTable`, from the current level out
+pub struct EnclosingMetadata<
+    'a,
+    K: Hash + Eq + Clone,
+    V: Clone,
+    P: PointerFamily,
+    S: BuildHasher,
+    M,
+>(Option<&'a SymbolTable<K, V, P, S, M>>);
+
+impl<'a, K: Hash + Eq + Clone, V: Clone, P: PointerFamily, S: BuildHasher, M> Debug
+    for EnclosingMetadata<'a, K, V, P, S, M>
+{
+    #[inline]
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        fmt.debug_struct("EnclosingMetadata")
+            .field("depth", &self.0.map(|table| table.depth))
+            .finish()
+    }
+}
+
+impl<'a, K: Hash + Eq + Clone, V: Clone, P: PointerFamily, S: BuildHasher, M> Clone
+    for EnclosingMetadata<'a, K, V, P, S, M>
+{
+    #[inline]
+    fn clone(&self) -> Self {
+        EnclosingMetadata(self.0)
+    }
+}
+
+impl<'a, K: Hash + Eq + Clone, V: Clone, P: PointerFamily, S: BuildHasher, M> Iterator
+    for EnclosingMetadata<'a, K, V, P, S, M>
+{
+    type Item = &'a M;
+    #[inline]
+    fn next(&mut self) -> Option<&'a M> {
+        let layer = self.0?;
+        self.0 = layer.prev.as_deref();
+        Some(&layer.metadata)
+    }
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.0.map_or(0, |table| table.depth + 1);
+        (len, Some(len))
+    }
+}
+
+impl<'a, K: Hash + Eq + Clone, V: Clone, P: PointerFamily, S: BuildHasher, M> ExactSizeIterator
+    for EnclosingMetadata<'a, K, V, P, S, M>
+{
+}
+
+/// An iterator over the definitions of a key in a `SymbolTable`, from most to least recent, along with their depths
+pub struct Defs<'a, K: Hash + Eq + Clone, V: Clone, P: PointerFamily, S: BuildHasher, M = ()> {
+    /// The key being looked up
+    key: Option<&'a K>,
+    /// The layer in which to look up the next definition
+    table: Option<&'a SymbolTable<K, V, P, S, M>>,
+}
+
+impl<'a, K: Hash + Eq + Clone + Debug, V: Clone, P: PointerFamily, S: BuildHasher, M> Debug
+    for Defs<'a, K, V, P, S, M>
+{
+    #[inline]
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        fmt.debug_struct("Defs")
+            .field("key", &self.key)
+            .field("depth", &self.table.map(|table| table.depth))
+            .finish()
+    }
+}
+
+impl<'a, K: Hash + Eq + Clone, V: Clone, P: PointerFamily, S: BuildHasher, M> Clone
+    for Defs<'a, K, V, P, S, M>
+{
+    #[inline]
+    fn clone(&self) -> Self {
+        Defs {
+            key: self.key,
+            table: self.table,
+        }
+    }
+}
+
+impl<'a, K: Hash + Eq + Clone, V: Clone, P: PointerFamily, S: BuildHasher, M> Iterator
+    for Defs<'a, K, V, P, S, M>
+{
+    type Item = (&'a V, usize);
+    #[inline]
+    fn next(&mut self) -> Option<(&'a V, usize)> {
+        let key = self.key?;
+        while let Some(layer) = self.table {
+            self.table = layer.prev.as_deref();
+            if let Some(value) = layer.symbols.get(key) {
+                return Some((&**value, layer.depth));
+            }
+        }
+        None
+    }
+}
+
+/// An iterator over the overloads of a key in a `SymbolTable`, from most to least recent, along with their depths
+pub struct Overloads<'a, K: Hash + Eq + Clone, V: Clone, P: PointerFamily, S: BuildHasher, M = ()> {
+    /// The key being looked up
+    key: Option<&'a K>,
+    /// The earlier definitions remaining at the level currently being iterated over, from least to most recent
+    earlier: &'a [Shared<V, P>],
+    /// The depth of the level currently being iterated over
+    depth: usize,
+    /// The layer in which to look up the next definition
+    table: Option<&'a SymbolTable<K, V, P, S, M>>,
+    /// Whether to continue on to enclosing levels
+    merged: bool,
+}
+
+impl<'a, K: Hash + Eq + Clone + Debug, V: Clone, P: PointerFamily, S: BuildHasher, M> Debug
+    for Overloads<'a, K, V, P, S, M>
+{
+    #[inline]
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        fmt.debug_struct("Overloads")
+            .field("key", &self.key)
+            .field("depth", &self.depth)
+            .field("merged", &self.merged)
+            .finish()
+    }
+}
+
+impl<'a, K: Hash + Eq + Clone, V: Clone, P: PointerFamily, S: BuildHasher, M> Clone
+    for Overloads<'a, K, V, P, S, M>
+{
+    #[inline]
+    fn clone(&self) -> Self {
+        Overloads {
+            key: self.key,
+            earlier: self.earlier,
+            depth: self.depth,
+            table: self.table,
+            merged: self.merged,
+        }
+    }
+}
+
+impl<'a, K: Hash + Eq + Clone, V: Clone, P: PointerFamily, S: BuildHasher, M> Iterator
+    for Overloads<'a, K, V, P, S, M>
+{
+    type Item = (&'a V, usize);
+    #[inline]
+    fn next(&mut self) -> Option<(&'a V, usize)> {
+        if let Some((value, earlier)) = self.earlier.split_last() {
+            self.earlier = earlier;
+            return Some((&**value, self.depth));
+        }
+        let key = self.key?;
+        while let Some(layer) = self.table {
+            self.table = if self.merged {
+                layer.prev.as_deref()
+            } else {
+                None
+            };
+            if let Some(value) = layer.symbols.get(key) {
+                self.earlier = layer.overloads_of(key);
+                self.depth = layer.depth;
+                return Some((&**value, layer.depth));
+            }
+        }
+        None
+    }
+}
+
+/// An iterator over the definitions made at a level of a `SymbolTable`
+type SymbolsIter<'a, K, V, P, S> =
+    <Symbols<K, V, P, S> as PersistentMap<K, Shared<V, P>, S>>::Iter<'a>;
+
+/// An iterator over the bindings visible in a `SymbolTable`
+///
+/// Walks each level of the table once, from the current level out, skipping the keys defined at an inner level.
+pub struct Iter<'a, K: Hash + Eq + Clone, V: Clone, P: PointerFamily, S: BuildHasher, M = ()> {
+    /// The definitions of the layer currently being iterated over
+    symbols: SymbolsIter<'a, K, V, P, S>,
+    /// The depth of the layer currently being iterated over
+    depth: usize,
+    /// The next layer to iterate over
+    table: Option<&'a SymbolTable<K, V, P, S, M>>,
+    /// The keys defined at the levels iterated over so far
+    seen: HashSet<&'a K, RandomState>,
+}
+
+impl<'a, K: Hash + Eq + Clone, V: Clone, P: PointerFamily, S: BuildHasher, M> Debug
+    for Iter<'a, K, V, P, S, M>
+{
+    #[inline]
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        fmt.debug_struct("Iter")
+            .field("depth", &self.depth)
+            .finish()
+    }
+}
+
+impl<'a, K: Hash + Eq + Clone, V: Clone, P: PointerFamily, S: BuildHasher, M> Iterator
+    for Iter<'a, K, V, P, S, M>
+{
+    type Item = (&'a K, &'a V);
+    #[inline]
+    fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        loop {
+            // Keys defined at the base level need not be remembered, since no level remains to shadow
+            let last = self.table.is_none();
+            let seen = &mut self.seen;
+            let visible = self.symbols.find(|(key, _)| {
+                if last {
+                    !seen.contains(key)
+                } else {
+                    seen.insert(key)
+                }
+            });
+            if let Some((key, value)) = visible {
+                return Some((key, &**value));
+            }
+            let layer = self.table?;
+            self.symbols = layer.symbols.iter();
+            self.depth = layer.depth;
+            self.table = layer.prev.as_deref();
+        }
+    }
+}
+
+/// An owning iterator over the bindings visible in a `SymbolTable`
+///
+/// Since the levels of a table may be shared with other tables, the bindings are cloned out of it.
+#[derive(Debug, Clone)]
+pub struct IntoIter<K, V>(std::vec::IntoIter<(K, V)>);
+
+impl<K, V> Iterator for IntoIter<K, V> {
+    type Item = (K, V);
+    #[inline]
+    fn next(&mut self) -> Option<(K, V)> {
+        self.0.next()
+    }
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<K, V> ExactSizeIterator for IntoIter<K, V> {}
+
+/// An iterator over the bindings defined at the current level of a `SymbolTable`
+pub struct IterTop<'a, K: Hash + Eq + Clone, V: Clone, P: PointerFamily, S: BuildHasher>(
+    SymbolsIter<'a, K, V, P, S>,
+)
+where
+    SymbolTable<K, V, P, S>: 'a;
+
+impl<'a, K: Hash + Eq + Clone, V: Clone, P: PointerFamily, S: BuildHasher> Debug
+    for IterTop<'a, K, V, P, S>
+where
+    SymbolTable<K, V, P, S>: 'a,
+{
+    #[inline]
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        fmt.debug_struct("IterTop").finish()
+    }
+}
+
+impl<'a, K: Hash + Eq + Clone, V: Clone, P: PointerFamily, S: BuildHasher> Iterator
+    for IterTop<'a, K, V, P, S>
+where
+    SymbolTable<K, V, P, S>: 'a,
+{
+    type Item = (&'a K, &'a V);
+    #[inline]
+    fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        self.0.next().map(|(key, value)| (key, &**value))
+    }
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+/// Levels pushed via `push` or `jump` carry the default metadata
+impl<K: Hash + Eq + Clone, V: Clone, P: PointerFamily, S: BuildHasher, M: Clone + Default>
+    SymbolMap<K> for SymbolTable<K, V, P, S, M>
+{
+    type Value = V;
+    type Defs<'a>
+        = Defs<'a, K, V, P, S, M>
+    where
+        Self: 'a;
+    type Iter<'a>
+        = Iter<'a, K, V, P, S, M>
+    where
+        Self: 'a;
+    type IterTop<'a>
+        = IterTop<'a, K, V, P, S>
+    where
+        Self: 'a;
+    #[inline]
+    fn insert(&mut self, key: K, value: Self::Value) {
+        self.clear_overloads(&key);
+        self.insert_latest(key, value);
+    }
+    #[inline]
+    fn try_insert(&mut self, key: K, value: Self::Value) -> Result<(), AlreadyDefined<'_, V>> {
+        if self.symbols.contains_key(&key) {
+            let existing = &**self.symbols.get(&key).expect("key is defined");
+            return Err(AlreadyDefined { value, existing });
+        }
+        self.insert(key, value);
+        Ok(())
+    }
+    #[inline]
+    fn get<Q>(&self, key: &Q) -> Option<&Self::Value>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        self.lookup(key).map(|(_, value, _)| &**value)
+    }
+    #[inline]
+    fn get_full<Q>(&self, key: &Q) -> Option<(&Self::Value, usize)>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        self.lookup(key).map(|(_, value, depth)| (&**value, depth))
+    }
+    #[inline]
+    fn get_defs<Q>(&self, key: &Q) -> Defs<'_, K, V, P, S, M>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        match self.lookup(key) {
+            Some((key, _, depth)) => Defs {
+                key: Some(key),
+                table: self.layer(depth),
+            },
+            None => Defs {
+                key: None,
+                table: None,
+            },
+        }
+    }
+    #[inline]
+    fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        match &self.cache {
+            Some(cache) => cache.contains_key(key),
+            None => self.lookup(key).is_some(),
+        }
+    }
+    #[inline]
+    fn remove<Q>(&mut self, key: &Q) -> Option<Self::Value>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        let (key, value) = self.symbols.remove_with_key(key)?;
+        self.clear_overloads::<K>(&key);
+        let prev = self.prev.as_deref();
+        if let Some(cache) = &mut self.cache {
+            match prev.and_then(|prev| prev.lookup::<K>(&key)) {
+                Some((_, value, depth)) => {
+                    cache.insert(key, (Some(value.clone()), depth));
+                }
+                None => {
+                    cache.remove_with_key::<K>(&key);
+                }
+            }
+        }
+        Some(P::unwrap_or_clone(value))
+    }
+    #[inline]
+    fn iter(&self) -> Iter<'_, K, V, P, S, M> {
+        Iter {
+            symbols: self.symbols.iter(),
+            depth: self.depth,
+            table: self.prev.as_deref(),
+            seen: HashSet::default(),
+        }
+    }
+    #[inline]
+    fn iter_top(&self) -> IterTop<'_, K, V, P, S> {
+        IterTop(self.symbols.iter())
+    }
+    #[inline]
+    fn is_empty(&self) -> bool {
+        match &self.cache {
+            Some(cache) => cache.is_empty(),
+            None => {
+                self.symbols.is_empty() && self.prev.as_deref().is_none_or(|prev| prev.is_empty())
+            }
+        }
+    }
+    #[inline]
+    fn try_get_mut<Q>(&mut self, key: &Q) -> Option<&mut Self::Value>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        let (_, _, depth) = self.lookup(key)?;
+        self.get_mut_at(key, depth)
+    }
+    #[inline]
+    fn push(&mut self) {
+        self.push_with(M::default())
+    }
+    #[inline]
+    fn pop(&mut self) {
+        let _ = self.pop_with();
+    }
+    #[inline]
+    fn jump(&mut self, depth: usize) {
+        while self.depth < depth {
+            self.push()
+        }
+        if let Some(table) = self.layer(depth) {
+            if table.depth != self.depth {
+                *self = table.clone();
+            }
+        }
+    }
+    #[inline]
+    fn depth(&self) -> usize {
+        self.depth
+    }
+}
+
+impl<K: Hash + Eq + Clone, V: Clone, P: PointerFamily, S: BuildHasher, M: Clone + Default>
+    MutSymbolMap<K> for SymbolTable<K, V, P, S, M>
+{
+}
+
+/// Earlier overloads of a key are kept at the level they were made at, and are discarded along with it
+impl<K: Hash + Eq + Clone, V: Clone, P: PointerFamily, S: BuildHasher, M: Clone + Default>
+    OverloadSymbolMap<K> for SymbolTable<K, V, P, S, M>
+{
+    type Overloads<'a>
+        = Overloads<'a, K, V, P, S, M>
+    where
+        Self: 'a;
+    #[inline]
+    fn insert_overload(&mut self, key: K, value: V) {
+        self.push_overload(key, value)
+    }
+    #[inline]
+    fn get_all<Q>(&self, key: &Q) -> Overloads<'_, K, V, P, S, M>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        self.overloads(key, false)
+    }
+    #[inline]
+    fn get_all_merged<Q>(&self, key: &Q) -> Overloads<'_, K, V, P, S, M>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        self.overloads(key, true)
+    }
+}
+
+/// Builds a symbol table at depth 0, inserting each binding in order
+impl<
+        K: Hash + Eq + Clone,
+        V: Clone,
+        P: PointerFamily,
+        S: BuildHasher + Default,
+        M: Clone + Default,
+    > FromIterator<(K, V)> for SymbolTable<K, V, P, S, M>
+{
+    #[inline]
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut table = SymbolTable::default();
+        Extend::extend(&mut table, iter);
+        table
+    }
+}
+
+/// Inserts each binding in order at the current level
+impl<K: Hash + Eq + Clone, V: Clone, P: PointerFamily, S: BuildHasher, M: Clone + Default>
+    Extend<(K, V)> for SymbolTable<K, V, P, S, M>
+{
+    #[inline]
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        for (key, value) in iter {
+            self.insert(key, value)
+        }
+    }
+}
+
+/// Gets the most recent definition of a key
+///
+/// # Panics
+/// Panics if the key is not defined.
+impl<K, Q, V, P, S, M> Index<&Q> for SymbolTable<K, V, P, S, M>
+where
+    K: Hash + Eq + Clone + Borrow<Q>,
+    Q: ?Sized + Hash + Eq,
+    V: Clone,
+    P: PointerFamily,
+    S: BuildHasher,
+{
+    type Output = V;
+    #[inline]
+    fn index(&self, key: &Q) -> &V {
+        self.lookup(key)
+            .map(|(_, value, _)| &**value)
+            .expect("key is not defined")
+    }
+}
+
+/// Iterates over the visible bindings, i.e. the most recent definition of each key, in arbitrary order
+impl<'a, K: Hash + Eq + Clone, V: Clone, P: PointerFamily, S: BuildHasher, M: Clone + Default>
+    IntoIterator for &'a SymbolTable<K, V, P, S, M>
+{
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V, P, S, M>;
+    #[inline]
+    fn into_iter(self) -> Iter<'a, K, V, P, S, M> {
+        self.iter()
+    }
+}
+
+/// Iterates over the visible bindings, i.e. the most recent definition of each key, in arbitrary order
+impl<K: Hash + Eq + Clone, V: Clone, P: PointerFamily, S: BuildHasher, M: Clone + Default>
+    IntoIterator for SymbolTable<K, V, P, S, M>
+{
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+    #[inline]
+    fn into_iter(self) -> IntoIter<K, V> {
+        let bindings: Vec<_> = self
+            .iter()
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        IntoIter(bindings.into_iter())
+    }
+}
+
+/// An entry occupied by a definition in a `SymbolTable`
+pub struct OccupiedEntry<
+    'a,
+    K: Hash + Eq + Clone,
+    V: Clone,
+    P: PointerFamily,
+    S: BuildHasher,
+    M = (),
+> {
+    /// The symbol table containing the entry
+    table: &'a mut SymbolTable<K, V, P, S, M>,
+    /// The key of the entry
+    key: K,
+    /// The depth of the definition occupying the entry
+    depth: usize,
+}
+
+impl<
+        'a,
+        K: Hash + Eq + Clone + Debug,
+        V: Clone + Debug,
+        P: PointerFamily,
+        S: BuildHasher,
+        M: Clone,
+    > Debug for OccupiedEntry<'a, K, V, P, S, M>
+{
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        use symbolmap_trait::OccupiedEntry;
+        fmt.debug_struct("OccupiedEntry")
+            .field("key", &self.key)
+            .field("value", self.get())
+            .finish()
+    }
+}
+
+impl<'a, K: Hash + Eq + Clone, V: Clone, P: PointerFamily, S: BuildHasher, M: Clone>
+    symbolmap_trait::OccupiedEntry<'a, K, V> for OccupiedEntry<'a, K, V, P, S, M>
+{
+    #[inline]
+    fn key(&self) -> &K {
+        &self.key
+    }
+    #[inline]
+    fn get(&self) -> &V {
+        self.table
+            .layer(self.depth)
+            .and_then(|table| table.symbols.get(&self.key))
+            .map(|value| &**value)
+            .expect("occupied entry is defined")
+    }
+    #[inline]
+    fn get_mut(&mut self) -> &mut V {
+        self.table
+            .get_mut_at(&self.key, self.depth)
+            .expect("occupied entry is defined")
+    }
+    #[inline]
+    fn into_mut(self) -> &'a mut V {
+        self.table
+            .get_mut_at(&self.key, self.depth)
+            .expect("occupied entry is defined")
+    }
+    #[inline]
+    fn insert(&mut self, value: V) -> V {
+        std::mem::replace(self.get_mut(), value)
+    }
+    #[inline]
+    fn depth(&self) -> usize {
+        self.depth
+    }
+    #[inline]
+    fn is_current(&self) -> bool {
+        self.depth == self.table.depth
+    }
+    #[inline]
+    fn shadow(self, value: V) -> &'a mut V {
+        self.table.define(self.key, value)
+    }
+}
+
+/// A vacant entry in a `SymbolTable`
+pub struct VacantEntry<'a, K: Hash + Eq + Clone, V: Clone, P: PointerFamily, S: BuildHasher, M = ()>
+{
+    /// The symbol table containing the entry
+    table: &'a mut SymbolTable<K, V, P, S, M>,
+    /// The key of the entry
+    key: K,
+}
+
+impl<'a, K: Hash + Eq + Clone + Debug, V: Clone, P: PointerFamily, S: BuildHasher, M> Debug
+    for VacantEntry<'a, K, V, P, S, M>
+{
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        fmt.debug_struct("VacantEntry")
+            .field("key", &self.key)
+            .finish()
+    }
+}
+
+impl<'a, K: Hash + Eq + Clone, V: Clone, P: PointerFamily, S: BuildHasher, M: Clone>
+    symbolmap_trait::VacantEntry<'a, K, V> for VacantEntry<'a, K, V, P, S, M>
+{
+    #[inline]
+    fn key(&self) -> &K {
+        &self.key
+    }
+    #[inline]
+    fn insert(self, value: V) -> &'a mut V {
+        self.table.define(self.key, value)
+    }
+}
+
+impl<K: Hash + Eq + Clone, V: Clone, P: PointerFamily, S: BuildHasher, M: Clone + Default>
+    EntrySymbolMap<K> for SymbolTable<K, V, P, S, M>
+{
+    type Occupied<'a>
+        = OccupiedEntry<'a, K, V, P, S, M>
+    where
+        Self: 'a;
+    type Vacant<'a>
+        = VacantEntry<'a, K, V, P, S, M>
+    where
+        Self: 'a;
+    #[inline]
+    fn entry(&mut self, key: K) -> Entry<'_, K, Self> {
+        match self.lookup(&key) {
+            Some((_, _, depth)) => Entry::Occupied(OccupiedEntry {
+                table: self,
+                key,
+                depth,
+            }),
+            None => Entry::Vacant(VacantEntry { table: self, key }),
+        }
+    }
+}
+
+impl<K: Hash + Eq + Clone, V: Clone, P: PointerFamily, S: BuildHasher, M: Clone + Default>
+    SymbolStack<K> for SymbolTable<K, V, P, S, M>
+{
+    #[inline]
+    fn prev(&self) -> Option<&Self> {
+        self.prev.as_deref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use symbolmap_trait::testing;
+
+    #[allow(dead_code)]
+    fn symbol_table_tests<P: PointerFamily>() {
+        fn table<P: PointerFamily>(cached: bool) -> SymbolTable<&'static str, usize, P> {
+            let mut table = SymbolTable::new();
+            table.set_cached(cached);
+            table
+        }
+        testing::collection_symbol_table_test::<SymbolTable<&'static str, usize, P>>();
+        testing::prelude_symbol_table_test(SymbolTable::<&'static str, usize, P>::new);
+        testing::resolver_symbol_table_test(SymbolTable::<&'static str, usize, P>::new);
+        testing::usage_symbol_table_test(SymbolTable::<&'static str, usize, P>::new());
+        testing::boundary_symbol_table_test(SymbolTable::<&'static str, usize, P>::new());
+        testing::namespaced_symbol_table_test(SymbolTable::<_, usize, P>::new());
+        for &cached in &[false, true] {
+            testing::basic_symbol_table_test(&mut table::<P>(cached));
+            testing::mutation_symbol_table_test(&mut table::<P>(cached));
+            testing::depth_symbol_table_test(&mut table::<P>(cached));
+            testing::iteration_symbol_table_test(&mut table::<P>(cached));
+            testing::try_insert_symbol_table_test(&mut table::<P>(cached));
+            testing::scope_symbol_table_test(&mut table::<P>(cached));
+            testing::scope_guard_symbol_table_test(&mut table::<P>(cached));
+            testing::remove_symbol_table_test(&mut table::<P>(cached));
+            testing::entry_symbol_table_test(&mut table::<P>(cached));
+            testing::dyn_symbol_table_test(&mut table::<P>(cached));
+            testing::overload_symbol_table_test(&mut table::<P>(cached));
+            testing::model_symbol_table_test(|| {
+                let mut table = SymbolTable::<u8, usize, P>::new();
+                table.set_cached(cached);
+                table
+            });
+            outer_mutation_tests::<P>(cached);
+        }
+        level_metadata_tests::<P>();
+        enabling_cache_tests::<P>();
+        cache_sharing_tests::<P>();
+    }
+    #[allow(dead_code)]
+    fn level_metadata_tests<P: PointerFamily>() {
+        let mut table = SymbolTable::<&str, usize, P, RandomState, Option<&str>>::default();
+        *table.metadata_mut() = Some("module");
+        table.insert("x", 1);
+        table.push_with(Some("function"));
+        let snapshot = table.clone();
+        table.push();
+        assert_eq!(
+            table.enclosing_metadata().collect::<Vec<_>>(),
+            [&None, &Some("function"), &Some("module")]
+        );
+        assert_eq!(table.metadata_at(1), Some(&Some("function")));
+        assert_eq!(table.pop_with(), Ok(None));
+        assert_eq!(table.pop_with(), Ok(Some("function")));
+        assert_eq!(table.pop_with(), Err(PopError));
+        assert_eq!(table.metadata(), &Some("module"));
+        assert_eq!(snapshot.metadata(), &Some("function"));
+    }
+    #[allow(dead_code)]
+    fn enabling_cache_tests<P: PointerFamily>() {
+        let mut table = SymbolTable::<_, _, P>::new();
+        table.insert("x", 1);
+        table.insert("y", 2);
+        table.push();
+        table.insert("x", 3);
+        table.push();
+        assert!(!table.is_cached());
+        table.set_cached(true);
+        assert!(table.is_cached());
+        assert_eq!(table.get_full("x"), Some((&3, 1)));
+        assert_eq!(table.get_full("y"), Some((&2, 0)));
+        table.insert("y", 4);
+        assert_eq!(table.remove("y"), Some(4));
+        assert_eq!(table.get_full("y"), Some((&2, 0)));
+        table.pop();
+        assert!(!table.is_cached());
+        assert_eq!(table.get_full("x"), Some((&3, 1)));
+    }
+    /// A value counting the number of times it has been cloned
+    #[derive(Debug, PartialEq)]
+    struct Counted(usize, std::rc::Rc<std::cell::Cell<usize>>);
+
+    impl Clone for Counted {
+        fn clone(&self) -> Counted {
+            self.1.set(self.1.get() + 1);
+            Counted(self.0, self.1.clone())
+        }
+    }
+
+    #[allow(dead_code)]
+    fn cache_sharing_tests<P: PointerFamily>() {
+        let clones = std::rc::Rc::new(std::cell::Cell::new(0));
+        let counted = |value| Counted(value, clones.clone());
+        let mut table = SymbolTable::<_, _, P>::new();
+        table.insert("x", counted(1));
+        table.set_cached(true);
+        table.insert("y", counted(2));
+        let snapshot = table.clone();
+        table.push();
+        table.insert("x", counted(3));
+        table.insert_overload("x", counted(4));
+        assert_eq!(table.get("x").map(|value| value.0), Some(4));
+        assert_eq!(table.iter().count(), 2);
+        assert_eq!(clones.get(), 0);
+        // Removed definitions are only cloned if the family cannot take them out of a unique pointer
+        assert_eq!(table.remove("x").map(|value| value.0), Some(4));
+        assert_eq!(table.get("x").map(|value| value.0), Some(1));
+        table.pop();
+        let before = clones.get();
+        table.try_get_mut("y").expect("y is defined").0 = 5;
+        assert_eq!(clones.get(), before + 1);
+        assert_eq!(snapshot.get("y").map(|value| value.0), Some(2));
+    }
+    #[allow(dead_code)]
+    fn outer_mutation_tests<P: PointerFamily>(cached: bool) {
+        let mut table = SymbolTable::<_, _, P>::new();
+        table.set_cached(cached);
+        table.insert("x", 1);
+        table.push();
+        table.push();
+        let snapshot = table.clone();
+        *table.get_mut("x").unwrap() = 2;
+        assert_eq!(table.get_full("x"), Some((&2, 0)));
+        assert_eq!(snapshot.get("x"), Some(&1));
+        table.pop();
+        assert_eq!(table.get("x"), Some(&2));
+        table.push();
+        assert_eq!(table.get("x"), Some(&2));
+        table.jump(0);
+        assert_eq!(table.get("x"), Some(&2));
+        let mut snapshot = snapshot;
+        snapshot.pop();
+        assert_eq!(snapshot.get("x"), Some(&1));
+    }
+    /// Get the definitions of a key along with their depths
+    #[allow(dead_code)]
+    fn defs<T: SymbolMap<&'static str, Value = usize>>(
+        table: &T,
+        key: &'static str,
+    ) -> Vec<(usize, usize)> {
+        table.get_defs(key).map(|(v, d)| (*v, d)).collect()
+    }
+    /// Check that a table has one layer for each level, linked by `prev`
+    #[allow(dead_code)]
+    fn check_layers<P: PointerFamily>(table: &SymbolTable<&'static str, usize, P>) {
+        let mut layer = Some(table);
+        for depth in (0..=table.depth()).rev() {
+            let current = layer.expect("every level has a layer");
+            assert_eq!(current.depth(), depth);
+            layer = current.prev();
+        }
+        assert!(layer.is_none());
+    }
+    #[allow(dead_code)]
+    fn family_conversion_tests<P: PointerFamily, Q: PointerFamily>() {
+        for &cached in &[false, true] {
+            let mut table = SymbolTable::<&'static str, usize, P>::new();
+            table.set_cached(cached);
+            table.insert("x", 1);
+            table.insert("y", 2);
+            table.push();
+            table.insert("x", 3);
+            table.jump(3);
+            table.insert("z", 4);
+            table.insert("y", 5);
+            let converted = table.to_family::<Q>();
+            assert_eq!(converted.depth(), 3);
+            assert_eq!(converted.is_cached(), cached);
+            for key in &["x", "y", "z", "w"] {
+                assert_eq!(defs(&converted, key), defs(&table, key));
+            }
+            check_layers(&converted);
+        }
+    }
+    #[cfg(feature = "hayami")]
+    #[allow(dead_code)]
+    fn hayami_conversion_tests<P: PointerFamily>() {
+        let mut table = hayami::SymbolTable::<&'static str, usize>::new();
+        table.insert("x", 1);
+        table.insert("y", 2);
+        table.push();
+        table.insert("x", 3);
+        table.insert_overload("x", 4);
+        table.jump(3);
+        table.insert("z", 5);
+        table.insert("y", 6);
+        let converted = SymbolTable::<_, _, P>::from(&table);
+        assert_eq!(converted.depth(), 3);
+        for key in &["x", "y", "z", "w"] {
+            assert_eq!(defs(&converted, key), defs(&table, key));
+        }
+        assert_eq!(converted.get_full("x"), Some((&4, 1)));
+        assert_eq!(
+            converted.get_all("x").collect::<Vec<_>>(),
+            [(&4, 1), (&3, 1)]
+        );
+        check_layers(&converted);
+        let back = hayami::SymbolTable::from(converted);
+        assert_eq!(back.depth(), 3);
+        assert_eq!(back.check_invariants(), Ok(()));
+        assert_eq!(back.get_all("x").collect::<Vec<_>>(), [(&4, 1), (&3, 1)]);
+        for key in &["x", "y", "z", "w"] {
+            assert_eq!(defs(&back, key), defs(&table, key));
+        }
+        let mut back = back;
+        back.pop();
+        back.pop();
+        assert_eq!(back.get("x"), Some(&4));
+        back.pop();
+        assert_eq!(back.get("x"), Some(&1));
+    }
+    #[cfg(all(feature = "arc", feature = "rc"))]
+    #[test]
+    fn family_conversions_work() {
+        family_conversion_tests::<ArcFamily, RcFamily>();
+        family_conversion_tests::<RcFamily, ArcFamily>();
+    }
+    #[cfg(all(feature = "hayami", feature = "arc"))]
+    #[test]
+    fn arc_hayami_conversions_work() {
+        hayami_conversion_tests::<ArcFamily>()
+    }
+    #[cfg(all(feature = "hayami", feature = "rc"))]
+    #[test]
+    fn rc_hayami_conversions_work() {
+        hayami_conversion_tests::<RcFamily>()
+    }
+    #[cfg(feature = "rc")]
+    #[test]
+    fn rc_symbol_table_tests() {
+        symbol_table_tests::<RcFamily>()
+    }
+    #[cfg(feature = "arc")]
+    #[test]
+    fn arc_symbol_table_tests() {
+        symbol_table_tests::<ArcFamily>()
+    }
+    #[cfg(feature = "elysees")]
+    #[test]
+    fn elysees_symbol_table_tests() {
+        symbol_table_tests::<ElyseesFamily>()
+    }
+}
@@ -0,0 +1,80 @@
+/*!
+`serde` support for `SymbolTable`, which is serialized as a sequence of levels, from the base level to the current level.
+Each level consists of its metadata and the definitions made at that level only, so layers shared between levels are not
+duplicated; the earlier overloads of a key precede its most recent definition, and are restored as overloads on
+deserialization.
+*/
+use super::*;
+use serde::de::{self, Deserialize, Deserializer};
+use serde::ser::{Serialize, Serializer};
+
+/// The definitions made at a given level of a symbol table
+struct Bindings<'a, K: Hash + Eq + Clone, V: Clone, P: PointerFamily, S: BuildHasher, M>(
+    &'a SymbolTable<K, V, P, S, M>,
+);
+
+impl<'a, K, V, P, S, M> Serialize for Bindings<'a, K, V, P, S, M>
+where
+    K: Hash + Eq + Clone + Serialize,
+    V: Clone + Serialize,
+    P: PointerFamily,
+    S: BuildHasher,
+{
+    fn serialize<Se: Serializer>(&self, serializer: Se) -> Result<Se::Ok, Se::Error> {
+        let table = self.0;
+        serializer.collect_seq(table.symbols.iter().flat_map(|(key, value)| {
+            table
+                .overloads_of(key)
+                .iter()
+                .chain(std::iter::once(value))
+                .map(move |value| (key, &**value))
+        }))
+    }
+}
+
+impl<K, V, P, S, M> Serialize for SymbolTable<K, V, P, S, M>
+where
+    K: Hash + Eq + Clone + Serialize,
+    V: Clone + Serialize,
+    P: PointerFamily,
+    S: BuildHasher,
+    M: Serialize,
+{
+    fn serialize<Se: Serializer>(&self, serializer: Se) -> Result<Se::Ok, Se::Error> {
+        let mut layers = Vec::with_capacity(self.depth + 1);
+        let mut layer = Some(self);
+        while let Some(table) = layer {
+            layers.push((&table.metadata, Bindings(table)));
+            layer = table.prev.as_deref();
+        }
+        serializer.collect_seq(layers.iter().rev())
+    }
+}
+
+impl<'de, K, V, P, S, M> Deserialize<'de> for SymbolTable<K, V, P, S, M>
+where
+    K: Hash + Eq + Clone + Deserialize<'de>,
+    V: Clone + Deserialize<'de>,
+    P: PointerFamily,
+    S: BuildHasher + Default,
+    M: Clone + Deserialize<'de>,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut levels = Vec::<(M, Vec<(K, V)>)>::deserialize(deserializer)?.into_iter();
+        let Some((metadata, mut level)) = levels.next() else {
+            return Err(de::Error::custom("symbol table must have a base level"));
+        };
+        let mut table = SymbolTable::with_metadata(metadata);
+        loop {
+            for (key, value) in level {
+                table.push_overload(key, value);
+            }
+            let Some((metadata, next)) = levels.next() else {
+                break;
+            };
+            table.push_with(metadata);
+            level = next;
+        }
+        Ok(table)
+    }
+}
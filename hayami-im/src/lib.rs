@@ -6,204 +6,153 @@ For a faster implementation which is limited to a single thread only, see the `h
 #![deny(missing_docs, unsafe_code, missing_debug_implementations)]
 
 use ahash::RandomState;
-use im::HashMap;
-use std::borrow::Borrow;
-use std::fmt::{self, Debug, Formatter};
-use std::hash::Hash;
-use std::hash::{BuildHasher, Hasher};
 
-pub use symbolmap_trait::{MutSymbolMap, SymbolMap, SymbolStack};
+pub use hayami_im_core::{
+    AlreadyDefined, DynSymbolMap, Entry, EntrySymbolMap, IntoIter, MutSymbolMap, Namespaced,
+    NamespacedIter, OverloadSymbolMap, PopError, Prelude, SymbolMap, SymbolStack, WithPrelude,
+};
 
-/// The `Arc` in use
+/// The family of shared pointers in use
 ///
 /// Supports `elysees` (default) or `std`
 #[cfg(feature = "elysees")]
-#[allow(unused)]
-type Arc<T> = elysees::Arc<T>;
+pub type Family = hayami_im_core::ElyseesFamily;
+/// The family of shared pointers in use
+///
+/// Supports `elysees` (default) or `std`
 #[cfg(not(feature = "elysees"))]
-#[allow(unused)]
-type Arc<T> = std::sync::Arc<T>;
+pub type Family = hayami_im_core::ArcFamily;
 
 /**
 A symbol table implementation supporting snapshots, i.e. an `O(1)` cloning operation.
+
+Each level of the table stores only the definitions made at that level, with lookups of outer definitions
+walking the chain of levels. Optionally, each level may also carry a cache of every visible definition,
+which makes lookups independent of the number of levels; see [`SymbolTable::set_cached`].
 */
-pub struct SymbolTable<K: Hash + Eq, V, S: BuildHasher = RandomState> {
-    /// This layer of the symbol table
-    symbols: HashMap<K, V, S>,
-    /// The depth of this symbol table
-    depth: usize,
-    /// A link to the previous layer's table, forming a singly-linked list
-    prev: Option<Arc<SymbolTable<K, V, S>>>,
-}
+pub type SymbolTable<K, V, S = RandomState, M = ()> =
+    hayami_im_core::SymbolTable<K, V, Family, S, M>;
 
-impl<K: Hash + Eq, V, S: BuildHasher + Default> Default for SymbolTable<K, V, S> {
-    #[inline]
-    fn default() -> SymbolTable<K, V, S> {
-        SymbolTable {
-            symbols: HashMap::default(),
-            depth: 0,
-            prev: None,
-        }
-    }
-}
+/// An iterator over the definitions of a key in a `SymbolTable`, from most to least recent, along with their depths
+pub type Defs<'a, K, V, S = RandomState, M = ()> = hayami_im_core::Defs<'a, K, V, Family, S, M>;
 
-impl<K: Hash + Eq, V> SymbolTable<K, V> {
-    /// Create a new, empty symbol table
-    #[inline]
-    pub fn new() -> SymbolTable<K, V> {
-        Self::default()
-    }
-}
+/// An iterator over the overloads of a key in a `SymbolTable`, from most to least recent, along with their depths
+pub type Overloads<'a, K, V, S = RandomState, M = ()> =
+    hayami_im_core::Overloads<'a, K, V, Family, S, M>;
 
-impl<K: Hash + Eq, V, S: BuildHasher> SymbolTable<K, V, S> {
-    /// Get a reference to the table's BuildHasher
-    #[inline]
-    pub fn hasher(&self) -> &std::sync::Arc<S> {
-        self.symbols.hasher()
-    }
-    /// Construct an empty hash map using the provided hasher.
-    #[inline]
-    pub fn with_hasher<RS>(hasher: RS) -> SymbolTable<K, V, S>
-    where
-        std::sync::Arc<S>: From<RS>,
-    {
-        SymbolTable {
-            symbols: HashMap::with_hasher(hasher),
-            depth: 0,
-            prev: None,
-        }
-    }
-}
+/// An iterator over the bindings visible in a `SymbolTable`
+pub type Iter<'a, K, V, S = RandomState, M = ()> = hayami_im_core::Iter<'a, K, V, Family, S, M>;
 
-impl<K: Hash + Eq, V: PartialEq, S: BuildHasher> PartialEq for SymbolTable<K, V, S> {
-    #[inline]
-    fn eq(&self, other: &Self) -> bool {
-        //TODO: think about comparing previous tables...
-        self.depth == other.depth && self.symbols == other.symbols && self.prev == other.prev
-    }
-}
-
-impl<K: Hash + Eq, V: Eq, S: BuildHasher> Eq for SymbolTable<K, V, S> {}
-
-impl<K: Hash + Eq, V: Hash, S: BuildHasher> Hash for SymbolTable<K, V, S> {
-    fn hash<H: Hasher>(&self, hasher: &mut H) {
-        //TODO: think about hashing previous tables...
-        self.symbols.hash(hasher);
-        self.depth.hash(hasher);
-        self.prev.hash(hasher);
-    }
-}
+/// An iterator over the bindings defined at the current level of a `SymbolTable`
+pub type IterTop<'a, K, V, S = RandomState> = hayami_im_core::IterTop<'a, K, V, Family, S>;
 
-impl<K: Hash + Eq + Debug, V: Debug, S: BuildHasher> Debug for SymbolTable<K, V, S> {
-    #[inline]
-    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
-        fmt.debug_struct("SymbolTable")
-            .field("symbols", &self.symbols)
-            .field("depth", &self.depth)
-            .field("prev", &self.prev)
-            .finish()
-    }
-}
+/// An entry occupied by a definition in a `SymbolTable`
+pub type OccupiedEntry<'a, K, V, S = RandomState, M = ()> =
+    hayami_im_core::OccupiedEntry<'a, K, V, Family, S, M>;
 
-impl<K: Hash + Eq, V, S: BuildHasher> SymbolTable<K, V, S> {
-    /// Get an `Arc` to the previous layer's table, if there is any
-    #[inline]
-    pub fn get_prev(&self) -> Option<&Arc<SymbolTable<K, V, S>>> {
-        self.prev.as_ref()
-    }
-}
+/// A vacant entry in a `SymbolTable`
+pub type VacantEntry<'a, K, V, S = RandomState, M = ()> =
+    hayami_im_core::VacantEntry<'a, K, V, Family, S, M>;
 
-impl<K: Hash + Eq + Clone, V: Clone, S: BuildHasher> Clone for SymbolTable<K, V, S> {
-    #[inline]
-    fn clone(&self) -> SymbolTable<K, V, S> {
-        SymbolTable {
-            symbols: self.symbols.clone(),
-            depth: self.depth,
-            prev: self.prev.clone(),
-        }
-    }
-}
+/// An iterator over the metadata of the levels of a `SymbolTable`, from the current level out
+pub type EnclosingMetadata<'a, K, V, S = RandomState, M = ()> =
+    hayami_im_core::EnclosingMetadata<'a, K, V, Family, S, M>;
 
-impl<K: Hash + Eq + Clone, V: Clone, S: BuildHasher> SymbolTable<K, V, S> {
-    /// Get a new symbol table extending this one
-    #[inline]
-    pub fn extend(self) -> SymbolTable<K, V, S> {
-        let symbols = self.symbols.clone();
-        let depth = self.depth + 1;
-        SymbolTable {
-            symbols,
-            depth,
-            prev: Some(Arc::new(self)),
-        }
-    }
-}
+/// A symbol table with a separate set of bindings in each of several namespaces, which share a single stack of scopes
+pub type NamespacedTable<N, K, V, S = RandomState> = Namespaced<N, K, SymbolTable<(N, K), V, S>>;
 
-impl<K: Hash + Eq + Clone, V: Clone, S: BuildHasher> SymbolMap<K> for SymbolTable<K, V, S> {
-    type Value = V;
-    #[inline]
-    fn insert(&mut self, key: K, value: Self::Value) {
-        self.symbols.insert(key, value);
-    }
-    #[inline]
-    fn get<Q>(&self, key: &Q) -> Option<&Self::Value>
-    where
-        Q: ?Sized + Hash + Eq,
-        K: Borrow<Q>,
-    {
-        self.symbols.get(key)
-    }
-    #[inline]
-    fn contains_key<Q>(&self, key: &Q) -> bool
-    where
-        Q: ?Sized + Hash + Eq,
-        K: Borrow<Q>,
-    {
-        self.symbols.contains_key(key)
-    }
-    #[inline]
-    fn is_empty(&self) -> bool {
-        self.symbols.is_empty()
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn namespaced_table_works() {
+        let mut table = NamespacedTable::<u8, String, usize>::default();
+        table.insert(0, "x".into(), 1);
+        table.insert(1, "x".into(), 2);
+        let snapshot = table.clone();
+        table.push();
+        table.insert(1, "x".into(), 3);
+        assert_eq!(table.get(0, "x"), Some(&1));
+        assert_eq!(table.get_full(1, "x"), Some((&3, 1)));
+        assert_eq!(snapshot.get_full(1, "x"), Some((&2, 0)));
+        table.pop();
+        assert_eq!(table.get(1, "x"), Some(&2));
+        assert_eq!(table, snapshot);
     }
-    #[inline]
-    fn try_get_mut<Q>(&mut self, key: &Q) -> Option<&mut Self::Value>
-    where
-        Q: ?Sized + Hash + Eq,
-        K: Borrow<Q>,
-    {
-        self.symbols.get_mut(key)
+    #[test]
+    #[allow(deprecated)]
+    fn extend_forwards_to_extended() {
+        let mut table = SymbolTable::<&str, usize>::new();
+        table.insert("x", 1);
+        let mut extended = table.clone().extend();
+        assert_eq!(extended.depth(), 1);
+        Extend::extend(&mut extended, vec![("x", 2)]);
+        assert_eq!(extended.get_full("x"), Some((&2, 1)));
+        assert_eq!(table.get_full("x"), Some((&1, 0)));
     }
-    #[inline]
-    fn push(&mut self) {
-        self.prev = Some(Arc::new((*self).clone()));
-        self.depth += 1;
+    #[test]
+    fn symbol_table_is_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<SymbolTable<String, usize>>();
     }
-    #[inline]
-    fn pop(&mut self) {
-        if let Some(prev) = self.prev.as_deref() {
-            *self = (*prev).clone();
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_roundtrip_works() {
+        let mut table = SymbolTable::<String, usize>::new();
+        table.insert("x".into(), 1);
+        table.insert("y".into(), 2);
+        table.push();
+        table.insert("x".into(), 3);
+        table.insert("z".into(), 4);
+        table.push();
+        table.insert("y".into(), 5);
+        table.insert_overload("y".into(), 6);
+        let json = serde_json::to_string(&table).unwrap();
+        let mut levels: Vec<((), Vec<(String, usize)>)> = serde_json::from_str(&json).unwrap();
+        for (_, level) in &mut levels {
+            level.sort_unstable();
         }
-    }
-    #[inline]
-    fn depth(&self) -> usize {
-        self.depth
-    }
-}
-
-impl<K: Hash + Eq + Clone, V: Clone, S: BuildHasher> MutSymbolMap<K> for SymbolTable<K, V, S> {}
-
-impl<K: Hash + Eq + Clone, V: Clone, S: BuildHasher> SymbolStack<K> for SymbolTable<K, V, S> {
-    #[inline]
-    fn prev(&self) -> Option<&Self> {
-        self.prev.as_deref()
-    }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use symbolmap_trait::testing;
+        assert_eq!(
+            levels,
+            [
+                ((), vec![("x".to_string(), 1), ("y".to_string(), 2)]),
+                ((), vec![("x".to_string(), 3), ("z".to_string(), 4)]),
+                ((), vec![("y".to_string(), 5), ("y".to_string(), 6)]),
+            ]
+        );
+        let mut de: SymbolTable<String, usize> = serde_json::from_str(&json).unwrap();
+        assert_eq!(de.depth(), 2);
+        assert_eq!(de, table);
+        assert_eq!(de.get_defs("y").collect::<Vec<_>>(), [(&6, 2), (&2, 0)]);
+        assert_eq!(de.get_all("y").collect::<Vec<_>>(), [(&6, 2), (&5, 2)]);
+        assert_eq!(de.get_defs("x").collect::<Vec<_>>(), [(&3, 1), (&1, 0)]);
+        de.pop();
+        assert_eq!(de.get("y"), Some(&2));
+        de.pop();
+        assert_eq!(de.get("x"), Some(&1));
+        assert_eq!(de.get("z"), None);
+        assert!(serde_json::from_str::<SymbolTable<String, usize>>("[]").is_err());
+    }
+    #[cfg(feature = "serde")]
     #[test]
-    fn basic_symbol_table_test() {
-        testing::basic_symbol_table_test(&mut SymbolTable::new())
+    fn serde_roundtrip_keeps_metadata() {
+        let mut table =
+            SymbolTable::<String, usize, RandomState, String>::with_metadata("module".into());
+        table.insert("x".into(), 1);
+        table.push_with("function".into());
+        table.insert("x".into(), 2);
+        table.push_with("block".into());
+        let json = serde_json::to_string(&table).unwrap();
+        let mut de: SymbolTable<String, usize, RandomState, String> =
+            serde_json::from_str(&json).unwrap();
+        assert_eq!(de, table);
+        assert_eq!(
+            de.enclosing_metadata().collect::<Vec<_>>(),
+            ["block", "function", "module"]
+        );
+        assert_eq!(de.pop_with(), Ok("block".to_string()));
+        assert_eq!(de.pop_with(), Ok("function".to_string()));
+        assert_eq!(de.get_full("x"), Some((&1, 0)));
+        assert_eq!(de.metadata(), "module");
     }
 }
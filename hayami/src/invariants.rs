@@ -0,0 +1,302 @@
+/*!
+Validation of the internal invariants of `SymbolTable`
+*/
+use super::*;
+use std::collections::HashMap;
+
+/// A violation of one of the internal invariants of a `SymbolTable`
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum InvariantError {
+    /// The insertion log does not begin with the base level's marker
+    MissingBaseMarker,
+    /// The number of level markers in the insertion log does not match the depth of the table
+    LevelCountMismatch {
+        /// The number of level markers in the insertion log
+        levels: usize,
+        /// The depth of the table
+        depth: usize,
+    },
+    /// The number of levels carrying metadata does not match the depth of the table
+    MetadataCountMismatch {
+        /// The number of levels carrying metadata
+        levels: usize,
+        /// The depth of the table
+        depth: usize,
+    },
+    /// The position of the current level's marker in the insertion log is out of date
+    InsertionIxMismatch {
+        /// The position of the last level marker in the insertion log
+        expected: usize,
+        /// The recorded position
+        found: usize,
+    },
+    /// The level markers in the insertion log do not account for every key in the table
+    KeyCountMismatch {
+        /// The number of keys accounted for by the level markers
+        logged: usize,
+        /// The number of keys in the table
+        keys: usize,
+    },
+    /// An entry in the insertion log refers to a key which is not in the table
+    DanglingInsertion {
+        /// The position of the entry in the insertion log
+        position: usize,
+        /// The index of the key it refers to
+        index: usize,
+    },
+    /// A definition is deeper than the table
+    DefinitionTooDeep {
+        /// The index of the key
+        index: usize,
+        /// The depth of the definition
+        depth: usize,
+        /// The depth of the table
+        table_depth: usize,
+    },
+    /// A key's definitions are not ordered by depth
+    DefinitionsOutOfOrder {
+        /// The index of the key
+        index: usize,
+    },
+    /// A key has a definition at a level below the one it was first defined at
+    DefinitionBeforeIntroduction {
+        /// The index of the key
+        index: usize,
+        /// The depth of the definition
+        depth: usize,
+        /// The depth of the level the key was first defined at, according to the insertion log
+        introduced: usize,
+    },
+    /// The number of definitions of a key at some level does not match the insertion log
+    LogMismatch {
+        /// The index of the key
+        index: usize,
+        /// The depth of the level
+        depth: usize,
+        /// The number of definitions of the key at that level
+        definitions: usize,
+        /// The number of redefinitions of the key recorded at that level in the insertion log
+        logged: usize,
+    },
+    /// The recorded number of definitions is out of date
+    DefinedMismatch {
+        /// The number of definitions in the table
+        expected: usize,
+        /// The recorded number of definitions
+        found: usize,
+    },
+}
+
+impl fmt::Display for InvariantError {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        match self {
+            InvariantError::MissingBaseMarker => {
+                write!(fmt, "insertion log does not begin with a level marker")
+            }
+            InvariantError::LevelCountMismatch { levels, depth } => write!(
+                fmt,
+                "insertion log has {} level markers, but the table is at depth {}",
+                levels, depth
+            ),
+            InvariantError::MetadataCountMismatch { levels, depth } => write!(
+                fmt,
+                "{} levels carry metadata, but the table is at depth {}",
+                levels, depth
+            ),
+            InvariantError::InsertionIxMismatch { expected, found } => write!(
+                fmt,
+                "current level marker is at position {} of the insertion log, but {} was recorded",
+                expected, found
+            ),
+            InvariantError::KeyCountMismatch { logged, keys } => write!(
+                fmt,
+                "insertion log accounts for {} keys, but the table has {}",
+                logged, keys
+            ),
+            InvariantError::DanglingInsertion { position, index } => write!(
+                fmt,
+                "insertion log entry {} refers to nonexistent key {}",
+                position, index
+            ),
+            InvariantError::DefinitionTooDeep {
+                index,
+                depth,
+                table_depth,
+            } => write!(
+                fmt,
+                "key {} is defined at depth {}, but the table is at depth {}",
+                index, depth, table_depth
+            ),
+            InvariantError::DefinitionsOutOfOrder { index } => {
+                write!(fmt, "definitions of key {} are not ordered by depth", index)
+            }
+            InvariantError::DefinitionBeforeIntroduction {
+                index,
+                depth,
+                introduced,
+            } => write!(
+                fmt,
+                "key {} is defined at depth {}, but was first defined at depth {}",
+                index, depth, introduced
+            ),
+            InvariantError::LogMismatch {
+                index,
+                depth,
+                definitions,
+                logged,
+            } => write!(
+                fmt,
+                "key {} has {} definitions at depth {}, but the insertion log records {} redefinitions",
+                index, definitions, depth, logged
+            ),
+            InvariantError::DefinedMismatch { expected, found } => write!(
+                fmt,
+                "table has {} definitions, but {} were recorded",
+                expected, found
+            ),
+        }
+    }
+}
+
+impl std::error::Error for InvariantError {}
+
+impl<K: Hash + Eq, V, S: BuildHasher, M> SymbolTable<K, V, S, M> {
+    /// Check that the internal bookkeeping of this symbol table is consistent, returning the first inconsistency found
+    ///
+    /// This takes time linear in the size of the table, and should never fail unless there is a bug in `hayami`.
+    pub fn check_invariants(&self) -> Result<(), InvariantError> {
+        if self.insertions.first().map(|marker| *marker < 0) != Some(true) {
+            return Err(InvariantError::MissingBaseMarker);
+        }
+        // The index of the first key introduced at each level
+        let mut starts = Vec::with_capacity(self.depth + 1);
+        let mut keys = 0usize;
+        let mut marker = 0;
+        let mut logged = HashMap::new();
+        for (position, insertion) in self.insertions.iter().enumerate() {
+            if *insertion < 0 {
+                marker = position;
+                starts.push(keys);
+                keys = keys.saturating_add(insertion.unsigned_abs() - 1);
+            } else if *insertion as usize >= self.symbols.len() {
+                return Err(InvariantError::DanglingInsertion {
+                    position,
+                    index: *insertion as usize,
+                });
+            } else {
+                *logged
+                    .entry((*insertion as usize, starts.len() - 1))
+                    .or_insert(0) += 1;
+            }
+        }
+        if starts.len() != self.depth + 1 {
+            return Err(InvariantError::LevelCountMismatch {
+                levels: starts.len(),
+                depth: self.depth,
+            });
+        }
+        if self.metadata.len() != self.depth + 1 {
+            return Err(InvariantError::MetadataCountMismatch {
+                levels: self.metadata.len(),
+                depth: self.depth,
+            });
+        }
+        if marker != self.insertion_ix {
+            return Err(InvariantError::InsertionIxMismatch {
+                expected: marker,
+                found: self.insertion_ix,
+            });
+        }
+        if keys != self.symbols.len() {
+            return Err(InvariantError::KeyCountMismatch {
+                logged: keys,
+                keys: self.symbols.len(),
+            });
+        }
+        let mut defined = 0;
+        for (index, defs) in self.symbols.values().enumerate() {
+            defined += defs.len();
+            let introduced = starts.partition_point(|start| *start <= index) - 1;
+            let mut defs = defs.iter().map(|(_, depth)| *depth).peekable();
+            // A key always has a definition at the level it was first defined at, which is recorded by the level's
+            // marker rather than an entry in the insertion log. Definitions at other depths which are out of range are
+            // reported below.
+            let first = defs.peek().copied();
+            if first.is_none_or(|depth| depth > introduced && depth <= self.depth) {
+                return Err(InvariantError::LogMismatch {
+                    index,
+                    depth: introduced,
+                    definitions: 0,
+                    logged: logged.remove(&(index, introduced)).unwrap_or(0),
+                });
+            }
+            let mut prev = introduced;
+            while let Some(depth) = defs.next() {
+                if depth > self.depth {
+                    return Err(InvariantError::DefinitionTooDeep {
+                        index,
+                        depth,
+                        table_depth: self.depth,
+                    });
+                }
+                if depth < introduced {
+                    return Err(InvariantError::DefinitionBeforeIntroduction {
+                        index,
+                        depth,
+                        introduced,
+                    });
+                }
+                if depth < prev {
+                    return Err(InvariantError::DefinitionsOutOfOrder { index });
+                }
+                prev = depth;
+                let mut definitions = 1;
+                while defs.next_if_eq(&depth).is_some() {
+                    definitions += 1;
+                }
+                let logged = logged.remove(&(index, depth)).unwrap_or(0);
+                let expected = if depth == introduced {
+                    logged + 1
+                } else {
+                    logged
+                };
+                if definitions != expected {
+                    return Err(InvariantError::LogMismatch {
+                        index,
+                        depth,
+                        definitions,
+                        logged,
+                    });
+                }
+            }
+        }
+        if let Some(((index, depth), logged)) = logged.into_iter().min() {
+            return Err(InvariantError::LogMismatch {
+                index,
+                depth,
+                definitions: 0,
+                logged,
+            });
+        }
+        if defined != self.defined {
+            return Err(InvariantError::DefinedMismatch {
+                expected: defined,
+                found: self.defined,
+            });
+        }
+        Ok(())
+    }
+    /// Check the internal invariants of this symbol table if the `debug-invariants` feature is enabled in a debug build
+    ///
+    /// # Panics
+    /// Panics if an invariant is violated.
+    #[inline]
+    pub(crate) fn debug_check_invariants(&self) {
+        #[cfg(all(feature = "debug-invariants", debug_assertions))]
+        {
+            if let Err(err) = self.check_invariants() {
+                panic!("symbol table invariant violated: {}", err)
+            }
+        }
+    }
+}
@@ -4,33 +4,67 @@ A simple, general-use symbol table optimized for speed at the cost of some advan
 #![deny(missing_docs, unsafe_code, missing_debug_implementations)]
 
 use ahash::RandomState;
-use indexmap::IndexMap;
+use indexmap::{Equivalent, IndexMap};
 use std::borrow::Borrow;
 use std::fmt::{self, Debug, Formatter};
 use std::hash::BuildHasher;
 use std::hash::Hash;
+use std::iter::{FromIterator, Rev};
+use std::ops::{Index, Range};
+use std::slice;
+use std::vec;
 
-pub use symbolmap_trait::SymbolMap;
+mod invariants;
+#[cfg(feature = "serde")]
+mod serialize;
 
-/// A symbol table implementation optimized for speed
+pub use invariants::InvariantError;
+
+pub use symbolmap_trait::{
+    AlreadyDefined, DynSymbolMap, Entry, EntrySymbolMap, MutSymbolMap, Namespaced, NamespacedIter,
+    OverloadSymbolMap, PopError, Prelude, SymbolMap, WithPrelude,
+};
+
+/// A symbol table with a separate set of bindings in each of several namespaces, which share a single stack of scopes
+pub type NamespacedTable<N, K, V, S = RandomState> = Namespaced<N, K, SymbolTable<(N, K), V, S>>;
+
+/**
+A symbol table implementation optimized for speed
+
+Each level of the table carries a piece of metadata of type `M`, which is pushed along with it by
+[`SymbolTable::push_with`] and returned when it is popped by [`SymbolTable::pop_with`].
+*/
 #[derive(Clone)]
-pub struct SymbolTable<K: Hash + Eq, V, S: BuildHasher = RandomState> {
-    symbols: IndexMap<K, Vec<V>, S>,
+pub struct SymbolTable<K: Hash + Eq, V, S: BuildHasher = RandomState, M = ()> {
+    symbols: IndexMap<K, Vec<(V, usize)>, S>,
     depth: usize,
     insertion_ix: usize,
     defined: usize,
     insertions: Vec<isize>,
+    metadata: Vec<M>,
+    /// The truncations of this table, against which checkpoints are validated
+    truncations: Truncations,
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher + Default, M: Default> Default for SymbolTable<K, V, S, M> {
+    #[inline]
+    fn default() -> SymbolTable<K, V, S, M> {
+        SymbolTable::with_metadata(M::default())
+    }
 }
 
-impl<K: Hash + Eq, V, S: BuildHasher + Default> Default for SymbolTable<K, V, S> {
+impl<K: Hash + Eq, V, S: BuildHasher + Default, M> SymbolTable<K, V, S, M> {
+    /// Create a new symbol table whose base level carries the given metadata
     #[inline]
-    fn default() -> SymbolTable<K, V, S> {
+    pub fn with_metadata(metadata: M) -> SymbolTable<K, V, S, M> {
         SymbolTable {
             symbols: IndexMap::default(),
             depth: 0,
             insertion_ix: 0,
             defined: 0,
-            insertions: Vec::new(),
+            insertions: vec![-1],
+            metadata: vec![metadata],
+            truncations: Truncations::default(),
         }
     }
 }
@@ -44,7 +78,9 @@ impl<K: Hash + Eq, V, S: BuildHasher + Default> SymbolTable<K, V, S> {
             depth: 0,
             insertion_ix: 0,
             defined: 0,
-            insertions: Vec::new(),
+            insertions: vec![-1],
+            metadata: vec![()],
+            truncations: Truncations::default(),
         }
     }
     /// Create a new symbol table having the given capacity with the given `BuildHasher`
@@ -55,7 +91,9 @@ impl<K: Hash + Eq, V, S: BuildHasher + Default> SymbolTable<K, V, S> {
             depth: 0,
             insertion_ix: 0,
             defined: 0,
-            insertions: Vec::new(),
+            insertions: vec![-1],
+            metadata: vec![()],
+            truncations: Truncations::default(),
         }
     }
 }
@@ -69,7 +107,9 @@ impl<K: Hash + Eq, V> SymbolTable<K, V> {
             depth: 0,
             insertion_ix: 0,
             defined: 0,
-            insertions: Vec::new(),
+            insertions: vec![-1],
+            metadata: vec![()],
+            truncations: Truncations::default(),
         }
     }
     /// Create a new, empty symbol table
@@ -79,7 +119,151 @@ impl<K: Hash + Eq, V> SymbolTable<K, V> {
     }
 }
 
-impl<K: Hash + Eq, V: PartialEq, S: BuildHasher> PartialEq for SymbolTable<K, V, S> {
+/**
+A checkpoint in the history of a `SymbolTable`, which it can be rolled back to.
+
+A checkpoint belongs to the table it was made from, along with any clone of it. It is invalidated by popping the level
+it was made at, by removing or replacing a definition made before it at that level, or by rolling back to an earlier
+checkpoint.
+Rolling back to or committing a checkpoint which has been invalidated panics. Using a checkpoint of another table is a
+logic error, which panics whenever the checkpoint is inconsistent with this table.
+
+The table keeps no record of its checkpoints, so one which is dropped rather than committed costs nothing.
+*/
+#[derive(Debug, PartialEq, Eq)]
+#[must_use = "a checkpoint should be rolled back to or committed"]
+pub struct Checkpoint {
+    /// The number of truncations of the table
+    generation: u64,
+    /// The depth of the table
+    depth: usize,
+    /// The length of the insertion log
+    insertions: usize,
+    /// The number of keys in the table
+    symbols: usize,
+}
+
+/**
+The truncations of the insertion log and keys of a `SymbolTable`, which invalidate the checkpoints made before them
+
+Only the truncations which are not subsumed by a later, shorter one are kept, so their lengths are increasing, and there
+are never more than one more of them than there are insertions or keys.
+*/
+#[derive(Debug, Clone, Default)]
+struct Truncations {
+    /// The number of truncations so far
+    generation: u64,
+    /// The generation of each truncation of the insertion log, along with its length afterwards
+    insertions: Vec<(u64, usize)>,
+    /// The generation of each truncation of the keys, along with their number afterwards
+    symbols: Vec<(u64, usize)>,
+}
+
+impl Truncations {
+    /// Record that the given ranges of the insertion log and keys have been truncated or rewritten
+    #[inline]
+    fn record(&mut self, insertions: Range<usize>, symbols: Range<usize>) {
+        if insertions.is_empty() && symbols.is_empty() {
+            return;
+        }
+        self.generation += 1;
+        Self::record_in(&mut self.insertions, self.generation, insertions);
+        Self::record_in(&mut self.symbols, self.generation, symbols);
+    }
+    /// Record that a range of the insertion log or keys has been truncated or rewritten
+    #[inline]
+    fn record_in(truncations: &mut Vec<(u64, usize)>, generation: u64, range: Range<usize>) {
+        if range.is_empty() {
+            return;
+        }
+        while truncations.last().map(|(_, len)| *len >= range.start) == Some(true) {
+            truncations.pop();
+        }
+        truncations.push((generation, range.start));
+    }
+    /// Get the shortest length something has been truncated to after a given generation, if any
+    #[inline]
+    fn shortest_after(truncations: &[(u64, usize)], generation: u64) -> Option<usize> {
+        let ix = truncations.partition_point(|(truncated, _)| *truncated <= generation);
+        truncations.get(ix).map(|(_, len)| *len)
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher, M> SymbolTable<K, V, S, M> {
+    /// Make a checkpoint of the current state of this symbol table
+    #[inline]
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            generation: self.truncations.generation,
+            depth: self.depth,
+            insertions: self.insertions.len(),
+            symbols: self.symbols.len(),
+        }
+    }
+    /// Check that a checkpoint can be rolled back to
+    ///
+    /// # Panics
+    /// Panics if the checkpoint has been invalidated, or is inconsistent with this table.
+    #[inline]
+    fn check_checkpoint(&self, checkpoint: &Checkpoint) {
+        let truncations = &self.truncations;
+        let insertions =
+            Truncations::shortest_after(&truncations.insertions, checkpoint.generation)
+                .unwrap_or(usize::MAX)
+                .min(self.insertions.len());
+        let symbols = Truncations::shortest_after(&truncations.symbols, checkpoint.generation)
+            .unwrap_or(usize::MAX)
+            .min(self.symbols.len());
+        assert!(
+            checkpoint.generation <= truncations.generation
+                && checkpoint.depth <= self.depth
+                && checkpoint.insertions <= insertions
+                && checkpoint.symbols <= symbols,
+            "this checkpoint has been invalidated, or belongs to another table"
+        );
+    }
+    /// Roll this symbol table back to a checkpoint, popping every level pushed and undoing every insertion made since
+    ///
+    /// Mutations of existing definitions, e.g. via `get_mut`, are not undone.
+    ///
+    /// # Panics
+    /// Panics if the checkpoint has been invalidated, or is inconsistent with this table.
+    pub fn rollback(&mut self, checkpoint: Checkpoint) {
+        self.check_checkpoint(&checkpoint);
+        self.truncate(checkpoint.depth);
+        let insertions = self.insertions.len();
+        let symbols = self.symbols.len();
+        for insertion in self.insertions.drain(checkpoint.insertions..).rev() {
+            if let Some((_, entry)) = self.symbols.get_index_mut(insertion as usize) {
+                entry.pop();
+                self.defined -= 1;
+            }
+        }
+        let undefined = self.symbols.len() - checkpoint.symbols;
+        for ix in checkpoint.symbols..self.symbols.len() {
+            self.defined -= self.symbols[ix].len();
+        }
+        self.symbols.truncate(checkpoint.symbols);
+        self.insertions[self.insertion_ix] += undefined as isize;
+        self.truncations.record(
+            checkpoint.insertions..insertions,
+            checkpoint.symbols..symbols,
+        );
+        self.debug_check_invariants();
+    }
+    /// Commit to the changes made since a checkpoint, discarding it
+    ///
+    /// # Panics
+    /// Panics if the checkpoint has been invalidated, or is inconsistent with this table.
+    #[inline]
+    pub fn commit(&self, checkpoint: Checkpoint) {
+        self.check_checkpoint(&checkpoint);
+    }
+}
+
+impl<K: Hash + Eq, V: PartialEq, S: BuildHasher, M: PartialEq> PartialEq
+    for SymbolTable<K, V, S, M>
+{
     #[inline]
     fn eq(&self, other: &Self) -> bool {
         self.depth == other.depth
@@ -87,40 +271,423 @@ impl<K: Hash + Eq, V: PartialEq, S: BuildHasher> PartialEq for SymbolTable<K, V,
             && self.defined == other.defined
             && self.insertions == other.insertions
             && self.symbols == other.symbols
+            && self.metadata == other.metadata
     }
 }
 
-impl<K: Hash + Eq, V: Eq, S: BuildHasher> Eq for SymbolTable<K, V, S> {}
+impl<K: Hash + Eq, V: Eq, S: BuildHasher, M: Eq> Eq for SymbolTable<K, V, S, M> {}
 
-impl<K: Hash + Eq + Debug, V: Debug, S: BuildHasher> Debug for SymbolTable<K, V, S> {
+impl<K: Hash + Eq + Debug, V: Debug, S: BuildHasher, M: Debug> Debug for SymbolTable<K, V, S, M> {
     #[inline]
     fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
         fmt.debug_struct("SymbolTable")
             .field("symbols", &self.symbols)
             .field("depth", &self.depth)
             .field("insertions", &self.insertions)
+            .field("metadata", &self.metadata)
             .finish()
     }
 }
 
-impl<K: Hash + Eq, V, S: BuildHasher> SymbolMap<K> for SymbolTable<K, V, S> {
-    type Value = V;
+/// An iterator over the definitions of a key in a `SymbolTable`, from most to least recent, along with their depths
+///
+/// Only the most recent definition at each depth is yielded; see [`Overloads`] for every definition.
+#[derive(Debug, Clone)]
+pub struct Defs<'a, V> {
+    /// The definitions of the key which have not yet been yielded or skipped
+    defs: Rev<slice::Iter<'a, (V, usize)>>,
+    /// The depth of the last definition yielded, if any
+    depth: Option<usize>,
+}
+
+impl<'a, V> Defs<'a, V> {
+    /// Iterate over a slice of definitions, ordered from least to most recent
     #[inline]
-    fn insert(&mut self, key: K, value: Self::Value) {
+    fn new(defs: &'a [(V, usize)]) -> Defs<'a, V> {
+        Defs {
+            defs: defs.iter().rev(),
+            depth: None,
+        }
+    }
+}
+
+impl<'a, V> Iterator for Defs<'a, V> {
+    type Item = (&'a V, usize);
+    #[inline]
+    fn next(&mut self) -> Option<(&'a V, usize)> {
+        let last = self.depth;
+        let (value, depth) = self.defs.find(|(_, depth)| Some(*depth) != last)?;
+        self.depth = Some(*depth);
+        Some((value, *depth))
+    }
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.defs.size_hint().1)
+    }
+}
+
+/// An iterator over every definition of a key in a `SymbolTable`, including overloads, from most to least recent, along
+/// with their depths
+#[derive(Debug, Clone)]
+pub struct Overloads<'a, V>(Rev<slice::Iter<'a, (V, usize)>>);
+
+impl<'a, V> Iterator for Overloads<'a, V> {
+    type Item = (&'a V, usize);
+    #[inline]
+    fn next(&mut self) -> Option<(&'a V, usize)> {
+        self.0.next().map(|(value, depth)| (value, *depth))
+    }
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<'a, V> ExactSizeIterator for Overloads<'a, V> {}
+
+/// An iterator over the bindings visible in a `SymbolTable`
+#[derive(Debug, Clone)]
+pub struct Iter<'a, K, V>(indexmap::map::Iter<'a, K, Vec<(V, usize)>>);
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+    #[inline]
+    fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        self.0
+            .find_map(|(key, defs)| defs.last().map(|(value, _)| (key, value)))
+    }
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.0.size_hint().1)
+    }
+}
+
+/// An owning iterator over the bindings visible in a `SymbolTable`
+#[derive(Debug)]
+pub struct IntoIter<K, V>(indexmap::map::IntoIter<K, Vec<(V, usize)>>);
+
+impl<K, V> Iterator for IntoIter<K, V> {
+    type Item = (K, V);
+    #[inline]
+    fn next(&mut self) -> Option<(K, V)> {
+        self.0
+            .find_map(|(key, mut defs)| defs.pop().map(|(value, _)| (key, value)))
+    }
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.0.size_hint().1)
+    }
+}
+
+/// An iterator over the bindings defined at the current level of a `SymbolTable`
+#[derive(Debug, Clone)]
+pub struct IterTop<'a, K: Hash + Eq, V, S: BuildHasher, M = ()> {
+    /// The table being iterated over
+    table: &'a SymbolTable<K, V, S, M>,
+    /// The index of the first key first defined at this level
+    first_new: usize,
+    /// The index of the next key first defined at this level
+    new_ix: usize,
+    /// The remaining entries of this level's insertion log, recording the keys redefined at this level
+    redefined: slice::Iter<'a, isize>,
+    /// The indices of the keys redefined at this level which have been yielded and have several definitions at this
+    /// level, and hence appear several times in the insertion log
+    overloaded: Vec<usize>,
+}
+
+impl<'a, K: Hash + Eq, V, S: BuildHasher, M> Iterator for IterTop<'a, K, V, S, M> {
+    type Item = (&'a K, &'a V);
+    fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        let table = self.table;
+        while self.new_ix < table.symbols.len() {
+            let ix = self.new_ix;
+            self.new_ix += 1;
+            if let Some(binding) = table.top_binding(ix) {
+                return Some(binding);
+            }
+        }
+        let first_new = self.first_new;
+        let overloaded = &mut self.overloaded;
+        self.redefined.find_map(|ix| {
+            let ix = *ix as usize;
+            // Keys first defined at this level are yielded above
+            if ix >= first_new {
+                return None;
+            }
+            let binding = table.top_binding(ix)?;
+            let defs = &table.symbols[ix];
+            if defs.len() > 1 && defs[defs.len() - 2].1 == table.depth {
+                if overloaded.contains(&ix) {
+                    return None;
+                }
+                overloaded.push(ix);
+            }
+            Some(binding)
+        })
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher, M> SymbolTable<K, V, S, M> {
+    /// Push a level carrying the given metadata onto this symbol table
+    #[inline]
+    pub fn push_with(&mut self, metadata: M) {
+        self.insertion_ix = self.insertions.len();
+        self.insertions.push(-1);
+        self.metadata.push(metadata);
+        self.depth += 1;
+        self.debug_check_invariants();
+    }
+    /// Pop a level from this symbol table, returning its metadata, or failing if it is at depth 0
+    #[inline]
+    pub fn pop_with(&mut self) -> Result<M, PopError> {
+        if self.depth == 0 {
+            return Err(PopError);
+        }
+        let metadata = self.metadata.pop().expect("every level carries metadata");
+        self.truncate(self.depth - 1);
+        Ok(metadata)
+    }
+    /// Get the metadata of the current level
+    #[inline]
+    pub fn metadata(&self) -> &M {
+        self.metadata.last().expect("every level carries metadata")
+    }
+    /// Get the metadata of the current level, mutably
+    #[inline]
+    pub fn metadata_mut(&mut self) -> &mut M {
+        self.metadata
+            .last_mut()
+            .expect("every level carries metadata")
+    }
+    /// Get the metadata of the level at a given depth, if any
+    #[inline]
+    pub fn metadata_at(&self, depth: usize) -> Option<&M> {
+        self.metadata.get(depth)
+    }
+    /// Iterate over the metadata of the current level and each enclosing level, from the innermost out
+    #[inline]
+    pub fn enclosing_metadata(&self) -> Rev<slice::Iter<'_, M>> {
+        self.metadata.iter().rev()
+    }
+    /// Pop levels until this symbol table is at most at a given depth
+    fn truncate(&mut self, depth: usize) {
+        if self.depth <= depth {
+            return;
+        }
+        let insertions = self.insertions.len();
+        let symbols = self.symbols.len();
+        let mut levels = self.depth - depth;
+        let mut undefined = 0;
+        while let Some(insertion) = self.insertions.pop() {
+            if insertion < 0 {
+                undefined += (-insertion as usize) - 1;
+                levels -= 1;
+                if levels == 0 {
+                    break;
+                }
+            } else if let Some((_, entry)) = self.symbols.get_index_mut(insertion as usize) {
+                entry.pop();
+                self.defined -= 1;
+            }
+        }
+        let len = self.symbols.len() - undefined;
+        for ix in len..self.symbols.len() {
+            self.defined -= self.symbols[ix].len();
+        }
+        self.symbols.truncate(len);
+        self.metadata.truncate(depth + 1);
+        self.truncations
+            .record(self.insertions.len()..insertions, len..symbols);
+        self.depth = depth;
+        self.insertion_ix = self
+            .insertions
+            .iter()
+            .rposition(|insertion| *insertion < 0)
+            .unwrap_or(0);
+        self.debug_check_invariants();
+    }
+    /// Remove the definitions of a key at the current level, returning the most recent
+    fn remove_equivalent<Q: ?Sized + Hash + Equivalent<K>>(&mut self, key: &Q) -> Option<V> {
+        let depth = self.depth;
+        let (ix, _, defs) = self.symbols.get_full_mut(key)?;
+        let mut removed = None;
+        while defs.last().map(|(_, d)| *d == depth) == Some(true) {
+            let (value, _) = defs.pop().expect("key is defined");
+            removed.get_or_insert(value);
+            self.defined -= 1;
+        }
+        if removed.is_some() {
+            let insertions = self.insertions.len();
+            let symbols = self.symbols.len();
+            // A key first defined at this level has no definitions left, so it is dropped from the table, shifting the
+            // keys defined after it down by one
+            let dropped = ix >= self.first_new();
+            // Drop the key's redefinitions from this level's insertion log, and reindex the keys after it, in a single
+            // pass; entries at enclosing levels only refer to keys defined before this level, so are left as they are
+            let mut first = None;
+            let start = self.insertion_ix + 1;
+            let mut len = start;
+            for pos in start..self.insertions.len() {
+                let insertion = self.insertions[pos];
+                if insertion == ix as isize {
+                    first.get_or_insert(pos);
+                } else {
+                    self.insertions[len] = if dropped && insertion > ix as isize {
+                        insertion - 1
+                    } else {
+                        insertion
+                    };
+                    len += 1;
+                }
+            }
+            self.insertions.truncate(len);
+            if dropped {
+                self.symbols.shift_remove_index(ix);
+                self.insertions[self.insertion_ix] += 1;
+            }
+            // Checkpoints made after the earliest definition removed can no longer be rolled back to
+            let first = first.unwrap_or(insertions);
+            let first_key = if dropped { ix } else { symbols };
+            self.truncations
+                .record(first..insertions, first_key..symbols);
+        }
+        self.debug_check_invariants();
+        removed
+    }
+    /// Define a key at the current level, keeping any definitions of it made there as overloads, and returning its index
+    fn push_definition(&mut self, key: K, value: V) -> usize {
         let len = self.symbols.len();
         let entry = self.symbols.entry(key);
         let ix = entry.index();
         let entry = entry.or_default();
         let entry_len = entry.len();
-        entry.push(value);
-        if self.depth != 0 {
-            if ix == len && entry_len == 0 {
-                self.insertions[self.insertion_ix] -= 1;
-            } else {
-                self.insertions.push(ix as isize)
-            }
+        entry.push((value, self.depth));
+        if ix == len && entry_len == 0 {
+            self.insertions[self.insertion_ix] -= 1;
+        } else {
+            self.insertions.push(ix as isize)
         }
         self.defined += 1;
+        self.debug_check_invariants();
+        ix
+    }
+    /// Define the key at a given index at the current level, shadowing its definition at an enclosing level
+    fn shadow_at(&mut self, ix: usize, value: V) {
+        self.symbols[ix].push((value, self.depth));
+        self.insertions.push(ix as isize);
+        self.defined += 1;
+        self.debug_check_invariants();
+    }
+    /// Replace the definitions of the key at a given index made at the current level, of which there must be at least
+    /// one, with a single definition
+    ///
+    /// This invalidates the checkpoints made after the earliest of them, since rolling back to them cannot restore it.
+    fn replace_current(&mut self, ix: usize, value: V) {
+        let depth = self.depth;
+        let defs = &mut self.symbols[ix];
+        let current = defs.iter().rev().take_while(|(_, d)| *d == depth).count();
+        defs.truncate(defs.len() + 1 - current);
+        defs.last_mut()
+            .expect("key is defined at the current level")
+            .0 = value;
+        self.defined -= current - 1;
+        if ix >= self.first_new() {
+            // The earliest definition is recorded by this level's marker, so every entry for the key is an overload
+            if current > 1 {
+                let start = self.insertion_ix + 1;
+                let mut len = start;
+                for pos in start..self.insertions.len() {
+                    if self.insertions[pos] != ix as isize {
+                        self.insertions[len] = self.insertions[pos];
+                        len += 1;
+                    }
+                }
+                self.insertions.truncate(len);
+            }
+            self.truncations.record(0..0, ix..self.symbols.len());
+        } else {
+            // Keep the earliest entry for the key in this level's insertion log, dropping those of its overloads
+            let insertions = self.insertions.len();
+            let start = self.insertion_ix + 1;
+            let mut first = None;
+            let mut len = start;
+            for pos in start..insertions {
+                let insertion = self.insertions[pos];
+                if insertion == ix as isize {
+                    if first.is_some() {
+                        continue;
+                    }
+                    first = Some(len);
+                }
+                self.insertions[len] = insertion;
+                len += 1;
+            }
+            self.insertions.truncate(len);
+            let first = first.expect("redefinitions of enclosing keys are logged");
+            self.truncations.record(first..insertions, 0..0);
+        }
+        self.debug_check_invariants();
+    }
+    /// Get the index of the first key in `symbols` which was first defined at the current level
+    #[inline]
+    fn first_new(&self) -> usize {
+        let new = (-self.insertions[self.insertion_ix] as usize) - 1;
+        self.symbols.len() - new
+    }
+    /// Get the binding at a given index in `symbols`, if it was defined at the current level
+    #[inline]
+    fn top_binding(&self, ix: usize) -> Option<(&K, &V)> {
+        let (key, defs) = self.symbols.get_index(ix)?;
+        match defs.last() {
+            Some((value, depth)) if *depth == self.depth => Some((key, value)),
+            _ => None,
+        }
+    }
+}
+
+/// Levels pushed via `push` or `jump` carry the default metadata
+impl<K: Hash + Eq, V, S: BuildHasher, M: Default> SymbolMap<K> for SymbolTable<K, V, S, M> {
+    type Value = V;
+    type Defs<'a>
+        = Defs<'a, V>
+    where
+        Self: 'a;
+    type Iter<'a>
+        = Iter<'a, K, V>
+    where
+        Self: 'a;
+    type IterTop<'a>
+        = IterTop<'a, K, V, S, M>
+    where
+        Self: 'a;
+    /// Replaces every definition of the key made at the current level, invalidating the checkpoints made after the
+    /// earliest of them
+    #[inline]
+    fn insert(&mut self, key: K, value: Self::Value) {
+        let depth = self.depth;
+        if let Some((ix, _, defs)) = self.symbols.get_full(&key) {
+            if defs.last().map(|(_, d)| *d == depth) == Some(true) {
+                self.replace_current(ix, value);
+                return;
+            }
+        }
+        self.push_definition(key, value);
+    }
+    #[inline]
+    fn try_insert(&mut self, key: K, value: Self::Value) -> Result<(), AlreadyDefined<'_, V>> {
+        let depth = self.depth;
+        let ix = if let Some((ix, _, defs)) = self.symbols.get_full(&key) {
+            if defs.last().map(|(_, d)| *d == depth) == Some(true) {
+                let (existing, _) = self.symbols[ix].last().expect("key is defined");
+                return Err(AlreadyDefined { value, existing });
+            }
+            ix
+        } else {
+            self.push_definition(key, value);
+            return Ok(());
+        };
+        self.shadow_at(ix, value);
+        Ok(())
     }
     #[inline]
     fn get<Q>(&self, key: &Q) -> Option<&Self::Value>
@@ -129,15 +696,57 @@ impl<K: Hash + Eq, V, S: BuildHasher> SymbolMap<K> for SymbolTable<K, V, S> {
         K: Borrow<Q>,
     {
         let vec = self.symbols.get(key)?;
-        vec.last()
+        vec.last().map(|(value, _)| value)
+    }
+    #[inline]
+    fn get_full<Q>(&self, key: &Q) -> Option<(&Self::Value, usize)>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        let vec = self.symbols.get(key)?;
+        vec.last().map(|(value, depth)| (value, *depth))
+    }
+    #[inline]
+    fn get_defs<Q>(&self, key: &Q) -> Defs<'_, V>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        let defs = self.symbols.get(key).map(|vec| &vec[..]).unwrap_or(&[]);
+        Defs::new(defs)
     }
     #[inline]
-    fn try_get_mut<Q>(&mut self, _key: &Q) -> Option<&mut Self::Value>
+    fn try_get_mut<Q>(&mut self, key: &Q) -> Option<&mut Self::Value>
     where
         Q: ?Sized + Hash + Eq,
         K: Borrow<Q>,
     {
-        None
+        let vec = self.symbols.get_mut(key)?;
+        vec.last_mut().map(|(value, _)| value)
+    }
+    #[inline]
+    fn remove<Q>(&mut self, key: &Q) -> Option<Self::Value>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        self.remove_equivalent(key)
+    }
+    #[inline]
+    fn iter(&self) -> Iter<'_, K, V> {
+        Iter(self.symbols.iter())
+    }
+    #[inline]
+    fn iter_top(&self) -> IterTop<'_, K, V, S, M> {
+        let first_new = self.first_new();
+        IterTop {
+            table: self,
+            first_new,
+            new_ix: first_new,
+            redefined: self.insertions[self.insertion_ix + 1..].iter(),
+            overloaded: Vec::new(),
+        }
     }
     #[inline]
     fn is_empty(&self) -> bool {
@@ -145,37 +754,18 @@ impl<K: Hash + Eq, V, S: BuildHasher> SymbolMap<K> for SymbolTable<K, V, S> {
     }
     #[inline]
     fn push(&mut self) {
-        self.insertion_ix = self.insertions.len();
-        self.insertions.push(-1);
-        self.depth += 1;
+        self.push_with(M::default())
     }
     #[inline]
     fn pop(&mut self) {
-        if self.depth == 0 {
-            return;
-        }
-        self.depth -= 1;
-        while let Some(mut insertion) = self.insertions.pop() {
-            if insertion < 0 {
-                let undefined = (-insertion as usize) - 1;
-                self.defined -= undefined;
-                while insertion < -1 {
-                    insertion += 1;
-                    self.symbols.pop();
-                }
-                return;
-            }
-            if let Some((_, entry)) = self.symbols.get_index_mut(insertion as usize) {
-                entry.pop();
-                self.defined -= 1;
-            }
-        }
-        for (i, insertion) in self.insertions.iter().enumerate().rev() {
-            if *insertion < 0 {
-                self.insertion_ix = i;
-                break;
-            }
+        let _ = self.pop_with();
+    }
+    #[inline]
+    fn jump(&mut self, depth: usize) {
+        while self.depth < depth {
+            self.push()
         }
+        self.truncate(depth)
     }
     #[inline]
     fn depth(&self) -> usize {
@@ -183,15 +773,380 @@ impl<K: Hash + Eq, V, S: BuildHasher> SymbolMap<K> for SymbolTable<K, V, S> {
     }
 }
 
+impl<K: Hash + Eq, V, S: BuildHasher, M: Default> MutSymbolMap<K> for SymbolTable<K, V, S, M> {}
+
+/// Builds a symbol table at depth 0, inserting each binding in order
+impl<K: Hash + Eq, V, S: BuildHasher + Default, M: Default> FromIterator<(K, V)>
+    for SymbolTable<K, V, S, M>
+{
+    #[inline]
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut table = SymbolTable::default();
+        table.extend(iter);
+        table
+    }
+}
+
+/// Inserts each binding in order at the current level
+impl<K: Hash + Eq, V, S: BuildHasher, M: Default> Extend<(K, V)> for SymbolTable<K, V, S, M> {
+    #[inline]
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        for (key, value) in iter {
+            self.insert(key, value)
+        }
+    }
+}
+
+/// Gets the most recent definition of a key
+///
+/// # Panics
+/// Panics if the key is not defined.
+impl<K, Q, V, S, M> Index<&Q> for SymbolTable<K, V, S, M>
+where
+    K: Hash + Eq + Borrow<Q>,
+    Q: ?Sized + Hash + Eq,
+    S: BuildHasher,
+{
+    type Output = V;
+    #[inline]
+    fn index(&self, key: &Q) -> &V {
+        self.symbols
+            .get(key)
+            .and_then(|defs| defs.last())
+            .map(|(value, _)| value)
+            .expect("key is not defined")
+    }
+}
+
+/// Iterates over the visible bindings, i.e. the most recent definition of each key, in arbitrary order
+impl<'a, K: Hash + Eq, V, S: BuildHasher, M> IntoIterator for &'a SymbolTable<K, V, S, M> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+    #[inline]
+    fn into_iter(self) -> Iter<'a, K, V> {
+        Iter(self.symbols.iter())
+    }
+}
+
+/// Iterates over the visible bindings, i.e. the most recent definition of each key, in arbitrary order
+impl<K: Hash + Eq, V, S: BuildHasher, M> IntoIterator for SymbolTable<K, V, S, M> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+    #[inline]
+    fn into_iter(self) -> IntoIter<K, V> {
+        IntoIter(self.symbols.into_iter())
+    }
+}
+
+/// An entry occupied by a definition in a `SymbolTable`
+pub struct OccupiedEntry<'a, K: Hash + Eq, V, S: BuildHasher, M = ()> {
+    /// The symbol table containing the entry
+    table: &'a mut SymbolTable<K, V, S, M>,
+    /// The index of the entry's key in the table
+    ix: usize,
+}
+
+impl<'a, K: Hash + Eq + Debug, V: Debug, S: BuildHasher, M> Debug
+    for OccupiedEntry<'a, K, V, S, M>
+{
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        let (key, defs) = self
+            .table
+            .symbols
+            .get_index(self.ix)
+            .expect("entry is occupied");
+        fmt.debug_struct("OccupiedEntry")
+            .field("key", key)
+            .field("defs", defs)
+            .finish()
+    }
+}
+
+impl<'a, K: Hash + Eq, V, S: BuildHasher, M> OccupiedEntry<'a, K, V, S, M> {
+    /// Get the definition occupying this entry, along with its depth
+    #[inline]
+    fn def(&self) -> &(V, usize) {
+        self.table.symbols[self.ix]
+            .last()
+            .expect("entry is occupied")
+    }
+}
+
+impl<'a, K: Hash + Eq, V, S: BuildHasher, M> symbolmap_trait::OccupiedEntry<'a, K, V>
+    for OccupiedEntry<'a, K, V, S, M>
+{
+    #[inline]
+    fn key(&self) -> &K {
+        let (key, _) = self
+            .table
+            .symbols
+            .get_index(self.ix)
+            .expect("entry is occupied");
+        key
+    }
+    #[inline]
+    fn get(&self) -> &V {
+        &self.def().0
+    }
+    #[inline]
+    fn get_mut(&mut self) -> &mut V {
+        &mut self.table.symbols[self.ix]
+            .last_mut()
+            .expect("entry is occupied")
+            .0
+    }
+    #[inline]
+    fn into_mut(self) -> &'a mut V {
+        &mut self.table.symbols[self.ix]
+            .last_mut()
+            .expect("entry is occupied")
+            .0
+    }
+    #[inline]
+    fn insert(&mut self, value: V) -> V {
+        std::mem::replace(self.get_mut(), value)
+    }
+    #[inline]
+    fn depth(&self) -> usize {
+        self.def().1
+    }
+    #[inline]
+    fn is_current(&self) -> bool {
+        self.depth() == self.table.depth
+    }
+    #[inline]
+    fn shadow(self, value: V) -> &'a mut V {
+        if self.is_current() {
+            self.table.replace_current(self.ix, value);
+        } else {
+            self.table.shadow_at(self.ix, value);
+        }
+        self.into_mut()
+    }
+}
+
+/// A vacant entry in a `SymbolTable`
+pub struct VacantEntry<'a, K: Hash + Eq, V, S: BuildHasher, M = ()> {
+    /// The symbol table containing the entry
+    table: &'a mut SymbolTable<K, V, S, M>,
+    /// The key of the entry
+    key: K,
+}
+
+impl<'a, K: Hash + Eq + Debug, V, S: BuildHasher, M> Debug for VacantEntry<'a, K, V, S, M> {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        fmt.debug_struct("VacantEntry")
+            .field("key", &self.key)
+            .finish()
+    }
+}
+
+impl<'a, K: Hash + Eq, V, S: BuildHasher, M> symbolmap_trait::VacantEntry<'a, K, V>
+    for VacantEntry<'a, K, V, S, M>
+{
+    #[inline]
+    fn key(&self) -> &K {
+        &self.key
+    }
+    #[inline]
+    fn insert(self, value: V) -> &'a mut V {
+        let ix = self.table.push_definition(self.key, value);
+        &mut self.table.symbols[ix]
+            .last_mut()
+            .expect("entry is occupied")
+            .0
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher, M: Default> EntrySymbolMap<K> for SymbolTable<K, V, S, M> {
+    type Occupied<'a>
+        = OccupiedEntry<'a, K, V, S, M>
+    where
+        Self: 'a;
+    type Vacant<'a>
+        = VacantEntry<'a, K, V, S, M>
+    where
+        Self: 'a;
+    #[inline]
+    fn entry(&mut self, key: K) -> Entry<'_, K, Self> {
+        match self.symbols.get_index_of(&key) {
+            Some(ix) => Entry::Occupied(OccupiedEntry { table: self, ix }),
+            None => Entry::Vacant(VacantEntry { table: self, key }),
+        }
+    }
+}
+
+/// Overloads are kept alongside the other definitions of a key, and are replaced by `insert`
+impl<K: Hash + Eq, V, S: BuildHasher, M: Default> OverloadSymbolMap<K> for SymbolTable<K, V, S, M> {
+    type Overloads<'a>
+        = Overloads<'a, V>
+    where
+        Self: 'a;
+    #[inline]
+    fn insert_overload(&mut self, key: K, value: V) {
+        self.push_definition(key, value);
+    }
+    #[inline]
+    fn get_all<Q>(&self, key: &Q) -> Overloads<'_, V>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        let defs = self.symbols.get(key).map(|vec| &vec[..]).unwrap_or(&[]);
+        let innermost = match defs.last() {
+            Some((_, depth)) => *depth,
+            None => return Overloads([].iter().rev()),
+        };
+        let start = defs.partition_point(|(_, depth)| *depth < innermost);
+        Overloads(defs[start..].iter().rev())
+    }
+    #[inline]
+    fn get_all_merged<Q>(&self, key: &Q) -> Overloads<'_, V>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        let defs = self.symbols.get(key).map(|vec| &vec[..]).unwrap_or(&[]);
+        Overloads(defs.iter().rev())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::panic::{catch_unwind, AssertUnwindSafe};
     use symbolmap_trait::testing;
+    use symbolmap_trait::{OccupiedEntry as _, VacantEntry as _};
     #[test]
     fn basic_symbol_table_test() {
         testing::basic_symbol_table_test(&mut SymbolTable::new())
     }
     #[test]
+    fn mutation_symbol_table_test() {
+        testing::mutation_symbol_table_test(&mut SymbolTable::new())
+    }
+    #[test]
+    fn depth_symbol_table_test() {
+        testing::depth_symbol_table_test(&mut SymbolTable::new())
+    }
+    #[test]
+    fn iteration_symbol_table_test() {
+        testing::iteration_symbol_table_test(&mut SymbolTable::new())
+    }
+    #[test]
+    fn try_insert_symbol_table_test() {
+        testing::try_insert_symbol_table_test(&mut SymbolTable::new())
+    }
+    #[test]
+    fn scope_symbol_table_test() {
+        testing::scope_symbol_table_test(&mut SymbolTable::new())
+    }
+    #[test]
+    fn scope_guard_symbol_table_test() {
+        testing::scope_guard_symbol_table_test(&mut SymbolTable::new())
+    }
+    #[test]
+    fn remove_symbol_table_test() {
+        testing::remove_symbol_table_test(&mut SymbolTable::new())
+    }
+    #[test]
+    fn overload_symbol_table_test() {
+        testing::overload_symbol_table_test(&mut SymbolTable::new())
+    }
+    #[test]
+    fn entry_symbol_table_test() {
+        testing::entry_symbol_table_test(&mut SymbolTable::new())
+    }
+    #[test]
+    fn dyn_symbol_table_test() {
+        testing::dyn_symbol_table_test(&mut SymbolTable::new())
+    }
+    #[test]
+    fn collection_symbol_table_test() {
+        testing::collection_symbol_table_test::<SymbolTable<_, _>>()
+    }
+    #[test]
+    #[should_panic]
+    fn indexing_undefined_key_panics() {
+        let table: SymbolTable<&str, usize> = vec![("x", 1)].into_iter().collect();
+        let _ = table["y"];
+    }
+    #[test]
+    fn prelude_symbol_table_test() {
+        testing::prelude_symbol_table_test(SymbolTable::new)
+    }
+    #[test]
+    fn resolver_symbol_table_test() {
+        testing::resolver_symbol_table_test(SymbolTable::new)
+    }
+    #[test]
+    fn usage_symbol_table_test() {
+        testing::usage_symbol_table_test(SymbolTable::new())
+    }
+    #[test]
+    fn boundary_symbol_table_test() {
+        testing::boundary_symbol_table_test(SymbolTable::new())
+    }
+    #[test]
+    fn namespaced_symbol_table_test() {
+        testing::namespaced_symbol_table_test(SymbolTable::new())
+    }
+    #[test]
+    fn usage_model_symbol_table_test() {
+        testing::model_symbol_table_test(|| symbolmap_trait::Tracked::new(SymbolTable::new()))
+    }
+    #[test]
+    fn resolver_model_symbol_table_test() {
+        testing::model_symbol_table_test(|| symbolmap_trait::Resolving::new(SymbolTable::new()))
+    }
+    #[test]
+    fn prelude_model_symbol_table_test() {
+        let prelude: std::sync::Arc<Prelude<_, _>> = Default::default();
+        testing::model_symbol_table_test_with_base(
+            || WithPrelude::new(prelude.clone(), SymbolTable::new()),
+            1,
+        )
+    }
+    #[test]
+    fn model_symbol_table_test() {
+        testing::model_symbol_table_test(SymbolTable::new)
+    }
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "unbalanced push/pop within a scope guard")]
+    fn unbalanced_scope_guard_panics() {
+        let mut table = SymbolTable::<usize, usize>::new();
+        let mut scope = table.scope();
+        scope.push();
+    }
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "the level guarded by a scope guard has been popped")]
+    fn popping_guarded_level_panics() {
+        let mut table = SymbolTable::<usize, usize>::new();
+        table.push();
+        let mut scope = table.scope();
+        scope.pop();
+        scope.pop();
+    }
+    #[test]
+    #[cfg(not(debug_assertions))]
+    fn popping_guarded_level_leaves_table_alone() {
+        let mut table = SymbolTable::<usize, usize>::new();
+        table.push();
+        let mut scope = table.scope();
+        scope.pop();
+        scope.pop();
+        drop(scope);
+        assert_eq!(table.depth(), 0);
+        table.push();
+        table.push();
+        let mut scope = table.scope();
+        scope.pop();
+        drop(scope);
+        assert_eq!(table.depth(), 2);
+    }
+    #[test]
     fn inserting_back_twice_works() {
         let mut table = SymbolTable::<usize, usize>::new();
         table.insert(5, 3);
@@ -206,4 +1161,546 @@ mod tests {
         assert_eq!(table.get(&5), Some(&3));
         table.pop();
     }
+    #[test]
+    fn removing_temporaries_reclaims_keys() {
+        let mut table = SymbolTable::<usize, usize>::new();
+        table.insert(0, 0);
+        for i in 1..100 {
+            table.insert(i, i);
+            table.insert(i, i + 1);
+            assert_eq!(table.remove(&i), Some(i + 1));
+        }
+        assert_eq!(table.symbols.len(), 1);
+        assert_eq!(table.insertions, [-2]);
+        table.insert(1, 1);
+        table.insert(2, 2);
+        assert_eq!(table.remove(&1), Some(1));
+        assert_eq!(table.symbols.len(), 2);
+        assert_eq!(table.remove(&2), Some(2));
+        assert_eq!(table.symbols.len(), 1);
+        table.push();
+        table.insert(0, 1);
+        table.insert(3, 3);
+        assert_eq!(table.remove(&0), Some(1));
+        assert_eq!(table.remove(&3), Some(3));
+        assert_eq!(table.symbols.len(), 1);
+        assert_eq!(table.insertions, [-2, -1]);
+        assert_eq!(table.check_invariants(), Ok(()));
+    }
+    #[test]
+    fn removing_earlier_temporaries_drops_keys() {
+        let mut table = SymbolTable::<usize, usize>::new();
+        table.insert(0, 0);
+        table.push();
+        table.insert(1, 1);
+        table.insert(2, 2);
+        table.insert_overload(2, 3);
+        table.insert(0, 4);
+        table.insert(3, 5);
+        let checkpoint = table.checkpoint();
+        table.insert(4, 6);
+        assert_eq!(table.remove(&1), Some(1));
+        assert_eq!(table.symbols.len(), 4);
+        assert_eq!(table.check_invariants(), Ok(()));
+        assert_eq!(table.get(&1), None);
+        assert_eq!(table.get_all(&2).collect::<Vec<_>>(), [(&3, 1), (&2, 1)]);
+        let mut top: Vec<_> = table.iter_top().collect();
+        top.sort_unstable();
+        assert_eq!(top, [(&0, &4), (&2, &3), (&3, &5), (&4, &6)]);
+        assert_eq!(table.iter().count(), 4);
+        assert!(catch_unwind(AssertUnwindSafe(|| table.clone().rollback(checkpoint))).is_err());
+        let checkpoint = table.checkpoint();
+        table.insert(1, 7);
+        assert_eq!(table.remove(&2), Some(3));
+        assert_eq!(table.check_invariants(), Ok(()));
+        assert!(catch_unwind(AssertUnwindSafe(|| table.clone().rollback(checkpoint))).is_err());
+        table.insert(5, 8);
+        let checkpoint = table.checkpoint();
+        table.insert(6, 9);
+        table.insert_overload(5, 10);
+        table.rollback(checkpoint);
+        assert_eq!(table.get_all(&5).collect::<Vec<_>>(), [(&8, 1)]);
+        assert_eq!(table.get(&6), None);
+        table.pop();
+        assert_eq!(table.symbols.len(), 1);
+        assert_eq!(table.get(&0), Some(&0));
+        assert_eq!(table.check_invariants(), Ok(()));
+    }
+    #[test]
+    fn checkpoint_rollback_works() {
+        let mut table = SymbolTable::<&str, usize>::new();
+        table.insert("x", 1);
+        table.push();
+        table.insert("y", 2);
+        let checkpoint = table.checkpoint();
+        table.insert("x", 3);
+        table.insert("x", 4);
+        table.insert("z", 5);
+        table.insert("z", 6);
+        table.push();
+        table.insert("w", 7);
+        table.insert("x", 8);
+        table.push();
+        table.rollback(checkpoint);
+        assert_eq!(table.depth(), 1);
+        assert_eq!(table.get_full("x"), Some((&1, 0)));
+        assert_eq!(table.get_full("y"), Some((&2, 1)));
+        assert_eq!(table.get("z"), None);
+        assert_eq!(table.get("w"), None);
+        assert_eq!(table.iter_top().collect::<Vec<_>>(), [(&"y", &2)]);
+        table.insert("z", 9);
+        assert_eq!(table.get("z"), Some(&9));
+        table.pop();
+        assert_eq!(table.get("y"), None);
+        assert_eq!(table.get("z"), None);
+        let checkpoint = table.checkpoint();
+        table.insert("a", 10);
+        table.commit(checkpoint);
+        assert_eq!(table.get("a"), Some(&10));
+        let outer = table.checkpoint();
+        table.insert("b", 11);
+        let inner = table.checkpoint();
+        table.insert("c", 12);
+        table.insert("c", 13);
+        table.rollback(inner);
+        assert_eq!(table.get("a"), Some(&10));
+        assert_eq!(table.get("b"), Some(&11));
+        assert_eq!(table.get("c"), None);
+        table.rollback(outer);
+        assert_eq!(table.get("b"), None);
+        assert_eq!(table.remove("a"), Some(10));
+        assert_eq!(table.remove("x"), Some(1));
+        assert!(table.is_empty());
+    }
+    #[test]
+    fn dropped_checkpoints_are_free() {
+        fn speculate(table: &mut SymbolTable<usize, usize>, i: usize) -> Option<()> {
+            let _checkpoint = table.checkpoint();
+            table.push();
+            table.insert(i, i);
+            table.pop();
+            table.insert(i, i);
+            None?;
+            Some(())
+        }
+        let mut table = SymbolTable::<usize, usize>::new();
+        table.insert(0, 0);
+        let outer = table.checkpoint();
+        for i in 1..100 {
+            assert_eq!(speculate(&mut table, i), None);
+        }
+        assert_eq!(table.truncations.insertions.len(), 1);
+        assert!(table.truncations.symbols.len() <= table.symbols.len());
+        table.rollback(outer);
+        assert_eq!(table.iter().collect::<Vec<_>>(), [(&0, &0)]);
+        assert_eq!(table.truncations.symbols, [(100, 1)]);
+    }
+    #[test]
+    #[should_panic(expected = "this checkpoint has been invalidated")]
+    fn rollback_to_popped_level_panics() {
+        let mut table = SymbolTable::<usize, usize>::new();
+        table.push();
+        let checkpoint = table.checkpoint();
+        table.pop();
+        table.push();
+        table.rollback(checkpoint);
+    }
+    #[test]
+    fn removing_earlier_definition_invalidates_checkpoint() {
+        let mut table = SymbolTable::<usize, usize>::new();
+        table.insert(1, 1);
+        table.push();
+        table.insert(1, 2);
+        table.insert(2, 3);
+        let before = table.checkpoint();
+        table.insert(3, 4);
+        let after = table.checkpoint();
+        table.insert(3, 5);
+        assert_eq!(table.remove(&3), Some(5));
+        assert!(catch_unwind(AssertUnwindSafe(|| table.clone().rollback(after))).is_err());
+        table.rollback(before);
+        assert_eq!(table.get_full(&1), Some((&2, 1)));
+        assert_eq!(table.get(&3), None);
+        let before = table.checkpoint();
+        table.insert(4, 7);
+        let after = table.checkpoint();
+        assert_eq!(table.remove(&1), Some(2));
+        assert!(catch_unwind(AssertUnwindSafe(|| table.clone().rollback(before))).is_err());
+        assert!(catch_unwind(AssertUnwindSafe(|| table.clone().commit(after))).is_err());
+        assert_eq!(table.check_invariants(), Ok(()));
+    }
+    #[test]
+    fn replacing_earlier_definition_invalidates_checkpoint() {
+        let mut table = SymbolTable::<usize, usize>::new();
+        table.insert(1, 1);
+        table.push();
+        table.insert(1, 2);
+        let before = table.checkpoint();
+        let same = table.checkpoint();
+        table.insert(2, 3);
+        let after = table.checkpoint();
+        table.insert(2, 4);
+        table.insert(2, 5);
+        assert_eq!(table.insertions.len(), 3);
+        assert!(catch_unwind(AssertUnwindSafe(|| table.clone().rollback(after))).is_err());
+        let mut rolled_back = table.clone();
+        rolled_back.rollback(same);
+        assert_eq!(rolled_back.get(&2), None);
+        assert_eq!(rolled_back.get_full(&1), Some((&2, 1)));
+        table.insert(1, 6);
+        assert_eq!(table.get_defs(&1).collect::<Vec<_>>(), [(&6, 1), (&1, 0)]);
+        assert!(catch_unwind(AssertUnwindSafe(|| table.clone().rollback(before))).is_err());
+        assert_eq!(table.check_invariants(), Ok(()));
+    }
+    #[test]
+    fn redefining_at_same_level_does_not_grow_log() {
+        let mut table = SymbolTable::<usize, usize>::new();
+        table.insert(1, 1);
+        table.push();
+        for value in 0..100 {
+            table.insert(0, value);
+            table.insert(1, value);
+        }
+        assert_eq!(table.insertions, [-2, -2, 0]);
+        assert_eq!(table.get_defs(&1).count(), 2);
+        table.insert_overload(1, 100);
+        table.insert(1, 101);
+        assert_eq!(table.get_all(&1).collect::<Vec<_>>(), [(&101, 1)]);
+        assert_eq!(table.insertions, [-2, -2, 0]);
+        assert_eq!(table.iter_top().count(), 2);
+        table.pop();
+        assert_eq!(table.get(&1), Some(&1));
+        assert_eq!(table.check_invariants(), Ok(()));
+    }
+    #[test]
+    fn shadowing_entries_keeps_invariants() {
+        let mut table = SymbolTable::<usize, usize>::new();
+        table.insert(1, 1);
+        table.push();
+        table.insert_overload(1, 2);
+        table.insert_overload(1, 3);
+        match table.entry(1) {
+            Entry::Occupied(entry) => assert_eq!(*entry.shadow(4), 4),
+            Entry::Vacant(_) => panic!("key should be occupied"),
+        }
+        assert_eq!(
+            table.get_all_merged(&1).collect::<Vec<_>>(),
+            [(&4, 1), (&1, 0)]
+        );
+        assert_eq!(table.insertions, [-2, -1, 0]);
+        table.push();
+        match table.entry(1) {
+            Entry::Occupied(entry) => assert_eq!(*entry.shadow(5), 5),
+            Entry::Vacant(_) => panic!("key should be occupied"),
+        }
+        match table.entry(2) {
+            Entry::Occupied(_) => panic!("key should be vacant"),
+            Entry::Vacant(entry) => assert_eq!(*entry.insert(6), 6),
+        }
+        assert_eq!(table.check_invariants(), Ok(()));
+        table.pop();
+        assert_eq!(table.get_full(&1), Some((&4, 1)));
+        assert_eq!(table.get(&2), None);
+        assert_eq!(table.check_invariants(), Ok(()));
+    }
+    #[test]
+    fn rollback_invalidates_later_checkpoints() {
+        let mut table = SymbolTable::<usize, usize>::new();
+        let outer = table.checkpoint();
+        table.insert(1, 1);
+        let inner = table.checkpoint();
+        table.insert(2, 2);
+        table.rollback(outer);
+        assert!(catch_unwind(AssertUnwindSafe(|| table.clone().rollback(inner))).is_err());
+        assert!(table.is_empty());
+    }
+    #[test]
+    #[should_panic(expected = "belongs to another table")]
+    fn rollback_to_foreign_checkpoint_panics() {
+        let mut table = SymbolTable::<usize, usize>::new();
+        let mut other = SymbolTable::<usize, usize>::new();
+        other.push();
+        let checkpoint = other.checkpoint();
+        table.insert(1, 1);
+        table.rollback(checkpoint);
+    }
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_roundtrip_works() {
+        let mut table = SymbolTable::<String, usize>::new();
+        table.insert("x".into(), 1);
+        table.insert("y".into(), 2);
+        table.push();
+        table.insert("x".into(), 3);
+        table.insert("z".into(), 4);
+        table.push();
+        table.insert("y".into(), 5);
+        table.insert_overload("y".into(), 6);
+        let json = serde_json::to_string(&table).unwrap();
+        let mut de: SymbolTable<String, usize> = serde_json::from_str(&json).unwrap();
+        assert_eq!(de, table);
+        assert_eq!(de.depth(), 2);
+        assert_eq!(de.get_defs("y").collect::<Vec<_>>(), [(&6, 2), (&2, 0)]);
+        assert_eq!(
+            de.get_all_merged("y").collect::<Vec<_>>(),
+            [(&6, 2), (&5, 2), (&2, 0)]
+        );
+        de.insert("w".into(), 7);
+        de.pop();
+        assert_eq!(de.get("y"), Some(&2));
+        assert_eq!(de.get("w"), None);
+        de.pop();
+        assert_eq!(de.get("x"), Some(&1));
+        assert_eq!(de.get("z"), None);
+        assert!(serde_json::from_str::<SymbolTable<String, usize>>(
+            r#"{"symbols":{"x":[[1,0]]},"insertions":[-1],"metadata":[null]}"#
+        )
+        .is_err());
+        assert!(serde_json::from_str::<SymbolTable<String, usize>>(
+            r#"{"symbols":{"x":[[1,0],[2,0]]},"insertions":[-2],"metadata":[null]}"#
+        )
+        .is_err());
+        assert!(serde_json::from_str::<SymbolTable<String, usize>>(
+            r#"{"symbols":{"x":[[1,0]]},"insertions":[-2],"metadata":[]}"#
+        )
+        .is_err());
+        assert!(serde_json::from_str::<SymbolTable<String, usize>>(
+            r#"{"symbols":{"x":[[1,0]]},"insertions":[-2],"metadata":[null]}"#
+        )
+        .is_ok());
+    }
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_roundtrip_keeps_metadata() {
+        let mut table =
+            SymbolTable::<String, usize, RandomState, String>::with_metadata("module".into());
+        table.insert("x".into(), 1);
+        table.push_with("function".into());
+        table.insert("x".into(), 2);
+        table.push_with("block".into());
+        let json = serde_json::to_string(&table).unwrap();
+        let mut de: SymbolTable<String, usize, RandomState, String> =
+            serde_json::from_str(&json).unwrap();
+        assert_eq!(de, table);
+        assert_eq!(
+            de.enclosing_metadata().collect::<Vec<_>>(),
+            ["block", "function", "module"]
+        );
+        assert_eq!(de.pop_with(), Ok("block".to_string()));
+        assert_eq!(de.pop_with(), Ok("function".to_string()));
+        assert_eq!(de.get_full("x"), Some((&1, 0)));
+        assert_eq!(de.metadata(), "module");
+        assert!(
+            serde_json::from_str::<SymbolTable<String, usize, RandomState, String>>(
+                r#"{"symbols":{},"insertions":[-1,-1],"metadata":["module"]}"#
+            )
+            .is_err()
+        );
+    }
+    #[test]
+    fn invariants_hold_after_mutation() {
+        let mut table = SymbolTable::<usize, usize>::new();
+        table.insert(1, 1);
+        table.insert(2, 2);
+        table.push();
+        table.insert(1, 3);
+        table.insert(1, 4);
+        table.insert(3, 5);
+        assert_eq!(table.check_invariants(), Ok(()));
+        let checkpoint = table.checkpoint();
+        table.insert(2, 6);
+        table.insert(4, 7);
+        table.push();
+        table.insert(5, 8);
+        assert_eq!(table.check_invariants(), Ok(()));
+        table.rollback(checkpoint);
+        assert_eq!(table.check_invariants(), Ok(()));
+        assert_eq!(table.remove(&3), Some(5));
+        assert_eq!(table.remove(&1), Some(4));
+        assert_eq!(table.check_invariants(), Ok(()));
+        table.entry(3).or_insert(9);
+        table.entry(2).or_insert(10);
+        assert_eq!(table.check_invariants(), Ok(()));
+        table.jump(3);
+        table.insert(6, 11);
+        table.jump(0);
+        assert_eq!(table.check_invariants(), Ok(()));
+    }
+    #[test]
+    fn corrupted_tables_violate_invariants() {
+        let mut table = SymbolTable::<usize, usize>::new();
+        table.insert(1, 1);
+        table.push();
+        table.insert(1, 2);
+        table.insert(2, 3);
+        let mut corrupted = table.clone();
+        corrupted.defined += 1;
+        assert_eq!(
+            corrupted.check_invariants(),
+            Err(InvariantError::DefinedMismatch {
+                expected: 3,
+                found: 4
+            })
+        );
+        let mut corrupted = table.clone();
+        corrupted.depth = 2;
+        assert_eq!(
+            corrupted.check_invariants(),
+            Err(InvariantError::LevelCountMismatch {
+                levels: 2,
+                depth: 2
+            })
+        );
+        let mut corrupted = table.clone();
+        corrupted.insertions.push(0);
+        assert_eq!(
+            corrupted.check_invariants(),
+            Err(InvariantError::LogMismatch {
+                index: 0,
+                depth: 1,
+                definitions: 1,
+                logged: 2
+            })
+        );
+        let mut corrupted = table.clone();
+        corrupted.insertions[corrupted.insertion_ix] -= 1;
+        assert_eq!(
+            corrupted.check_invariants(),
+            Err(InvariantError::KeyCountMismatch { logged: 3, keys: 2 })
+        );
+        let mut corrupted = table.clone();
+        corrupted.symbols[1][0].1 = 0;
+        assert_eq!(
+            corrupted.check_invariants(),
+            Err(InvariantError::DefinitionBeforeIntroduction {
+                index: 1,
+                depth: 0,
+                introduced: 1
+            })
+        );
+        let mut corrupted = table.clone();
+        corrupted.symbols[1].clear();
+        corrupted.defined -= 1;
+        assert_eq!(
+            corrupted.check_invariants(),
+            Err(InvariantError::LogMismatch {
+                index: 1,
+                depth: 1,
+                definitions: 0,
+                logged: 0
+            })
+        );
+        let mut corrupted = table;
+        corrupted.insertions[0] = 0;
+        assert_eq!(
+            corrupted.check_invariants(),
+            Err(InvariantError::MissingBaseMarker)
+        );
+    }
+    #[test]
+    fn level_metadata_works() {
+        let mut table = SymbolTable::<&str, usize, RandomState, Option<&str>>::default();
+        assert_eq!(table.metadata(), &None);
+        *table.metadata_mut() = Some("module");
+        table.insert("x", 1);
+        table.push_with(Some("function"));
+        table.insert("x", 2);
+        table.push();
+        table.push_with(Some("loop"));
+        assert_eq!(
+            table.enclosing_metadata().collect::<Vec<_>>(),
+            [&Some("loop"), &None, &Some("function"), &Some("module")]
+        );
+        assert_eq!(table.metadata_at(1), Some(&Some("function")));
+        assert_eq!(table.metadata_at(4), None);
+        let checkpoint = table.checkpoint();
+        table.push_with(Some("block"));
+        table.rollback(checkpoint);
+        assert_eq!(table.metadata(), &Some("loop"));
+        assert_eq!(table.pop_with(), Ok(Some("loop")));
+        table.jump(1);
+        assert_eq!(table.metadata(), &Some("function"));
+        assert_eq!(table.check_invariants(), Ok(()));
+        assert_eq!(table.pop_with(), Ok(Some("function")));
+        assert_eq!(table.get("x"), Some(&1));
+        assert_eq!(table.pop_with(), Err(PopError));
+        assert_eq!(table.metadata(), &Some("module"));
+        let mut corrupted = table.clone();
+        corrupted.metadata.push(None);
+        assert_eq!(
+            corrupted.check_invariants(),
+            Err(InvariantError::MetadataCountMismatch {
+                levels: 2,
+                depth: 0
+            })
+        );
+    }
+    #[test]
+    fn indexing_does_not_need_default_metadata() {
+        fn lookup<M>(table: &SymbolTable<&str, usize, RandomState, M>) -> (usize, usize) {
+            (table["x"], table.into_iter().count())
+        }
+        let mut table = SymbolTable::with_metadata("main");
+        table.extend(vec![("x", 1), ("y", 2)]);
+        assert_eq!(lookup(&table), (1, 2));
+    }
+    #[test]
+    fn namespaced_table_works() {
+        #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+        enum Namespace {
+            Type,
+            Value,
+            Macro,
+        }
+        use Namespace::*;
+        let mut table = NamespacedTable::<Namespace, String, usize>::default();
+        table.insert(Type, "x".into(), 1);
+        table.insert(Value, "x".into(), 2);
+        assert_eq!(table.get(Type, "x"), Some(&1));
+        assert_eq!(table.get(Value, "x"), Some(&2));
+        assert_eq!(table.get(Macro, "x"), None);
+        table.push();
+        table.insert(Value, "x".into(), 3);
+        table.insert(Macro, "m".into(), 4);
+        assert!(table.try_insert(Macro, "m".into(), 5).is_err());
+        assert_eq!(table.get_full(Type, "x"), Some((&1, 0)));
+        assert_eq!(table.get_full(Value, "x"), Some((&3, 1)));
+        assert_eq!(
+            table.get_defs(Value, "x").collect::<Vec<_>>(),
+            [(&3, 1), (&2, 0)]
+        );
+        *table.try_get_mut(Type, "x").unwrap() += 10;
+        assert_eq!(
+            table.iter_namespace(Value).collect::<Vec<_>>(),
+            [(Value, &"x".to_string(), &3)]
+        );
+        assert_eq!(table.iter().count(), 3);
+        assert_eq!(table.remove(Value, "x"), Some(3));
+        assert_eq!(table.remove(Type, "x"), None);
+        assert_eq!(table.get(Value, "x"), Some(&2));
+        assert_eq!(table.table().check_invariants(), Ok(()));
+        table.pop();
+        assert!(!table.contains_key(Macro, "m"));
+        assert_eq!(table.get(Type, "x"), Some(&11));
+        assert_eq!(table.try_pop(), Err(PopError));
+        table.jump(2);
+        assert_eq!(table.depth(), 2);
+        assert!(!table.is_empty());
+    }
+    #[test]
+    fn inserting_after_nested_pop_works() {
+        let mut table = SymbolTable::<usize, usize>::new();
+        table.push();
+        table.push();
+        table.insert(1, 1);
+        table.pop();
+        table.insert(2, 2);
+        table.insert(3, 3);
+        assert_eq!(table.get(&1), None);
+        assert_eq!(table.iter_top().count(), 2);
+        table.pop();
+        assert_eq!(table.get(&2), None);
+        assert_eq!(table.get(&3), None);
+        assert!(table.is_empty());
+    }
 }
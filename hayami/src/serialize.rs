@@ -0,0 +1,69 @@
+/*!
+`serde` support for `SymbolTable`
+*/
+use super::*;
+use serde::de::{self, Deserializer};
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+use serde::Deserialize;
+
+impl<K, V, S, M> Serialize for SymbolTable<K, V, S, M>
+where
+    K: Hash + Eq + Serialize,
+    V: Serialize,
+    S: BuildHasher,
+    M: Serialize,
+{
+    fn serialize<Se: Serializer>(&self, serializer: Se) -> Result<Se::Ok, Se::Error> {
+        let mut state = serializer.serialize_struct("SymbolTable", 3)?;
+        state.serialize_field("symbols", &self.symbols)?;
+        state.serialize_field("insertions", &self.insertions)?;
+        state.serialize_field("metadata", &self.metadata)?;
+        state.end()
+    }
+}
+
+/// The serialized form of a `SymbolTable`: the definitions of each key, the insertion log, and the metadata of each level
+#[derive(Deserialize)]
+#[serde(rename = "SymbolTable")]
+#[serde(bound(
+    deserialize = "K: Deserialize<'de> + Hash + Eq, V: Deserialize<'de>, S: BuildHasher + Default, M: Deserialize<'de>"
+))]
+struct SymbolTableData<K, V, S, M> {
+    symbols: IndexMap<K, Vec<(V, usize)>, S>,
+    insertions: Vec<isize>,
+    metadata: Vec<M>,
+}
+
+impl<'de, K, V, S, M> Deserialize<'de> for SymbolTable<K, V, S, M>
+where
+    K: Hash + Eq + Deserialize<'de>,
+    V: Deserialize<'de>,
+    S: BuildHasher + Default,
+    M: Deserialize<'de>,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let SymbolTableData {
+            symbols,
+            insertions,
+            metadata,
+        } = SymbolTableData::deserialize(deserializer)?;
+        let markers = insertions
+            .iter()
+            .filter(|insertion| **insertion < 0)
+            .count();
+        let table = SymbolTable {
+            depth: markers.saturating_sub(1),
+            metadata,
+            insertion_ix: insertions
+                .iter()
+                .rposition(|insertion| *insertion < 0)
+                .unwrap_or(0),
+            defined: symbols.values().map(Vec::len).sum(),
+            symbols,
+            insertions,
+            truncations: Truncations::default(),
+        };
+        table.check_invariants().map_err(de::Error::custom)?;
+        Ok(table)
+    }
+}
@@ -0,0 +1,277 @@
+/*!
+Levels tagged with the kind of scope they represent, for analysing which lookups cross function boundaries
+*/
+use super::*;
+use std::collections::HashSet;
+
+/// The kind of scope represented by a level of a symbol table
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub enum ScopeKind {
+    /// A block scope
+    #[default]
+    Block,
+    /// The body of a function. Lookups of bindings defined outside of a function scope capture them.
+    Function,
+    /// A module scope
+    Module,
+    /// The body of a loop
+    Loop,
+}
+
+impl ScopeKind {
+    /// Whether lookups crossing a scope of this kind capture the bindings they find
+    #[inline]
+    pub fn is_boundary(self) -> bool {
+        self == ScopeKind::Function
+    }
+}
+
+/// The result of looking up a key in a `Boundaries` symbol table, recording the boundaries it crossed
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Crossing<'a, V> {
+    /// The innermost definition of the key
+    pub value: &'a V,
+    /// The depth at which the key is defined
+    pub depth: usize,
+    /// The depths of the boundary levels between the definition and the current level, from the outermost in
+    pub boundaries: Vec<usize>,
+}
+
+/// A level popped from a `Boundaries` symbol table
+#[derive(Debug, Clone)]
+pub struct PoppedScope<K> {
+    /// The kind of scope the level represented
+    pub kind: ScopeKind,
+    /// The keys defined outside the level which were captured by lookups crossing it, which is always empty unless it is
+    /// a boundary
+    pub captures: HashSet<K>,
+}
+
+impl<K: Hash + Eq> PartialEq for PoppedScope<K> {
+    #[inline]
+    fn eq(&self, other: &PoppedScope<K>) -> bool {
+        self.kind == other.kind && self.captures == other.captures
+    }
+}
+
+impl<K: Hash + Eq> Eq for PoppedScope<K> {}
+
+/// The kind of a level of a `Boundaries` symbol table, along with the keys captured by it so far
+#[derive(Debug, Clone)]
+struct Scope<K> {
+    kind: ScopeKind,
+    captures: HashSet<K>,
+}
+
+impl<K> Scope<K> {
+    #[inline]
+    fn new(kind: ScopeKind) -> Scope<K> {
+        Scope {
+            kind,
+            captures: HashSet::new(),
+        }
+    }
+}
+
+/**
+A symbol table whose levels are tagged with a [`ScopeKind`], recording the keys captured by each function scope.
+
+Levels pushed via `SymbolMap::push` or `jump` are blocks, while other kinds of level may be pushed via
+[`push_kind`](Boundaries::push_kind). The base level is a module. Lookups via
+[`get_crossing`](Boundaries::get_crossing) report the boundaries they cross, and record the key as captured by each of
+them; these captures are returned when the level is popped via [`pop_scope`](Boundaries::pop_scope), and discarded if it
+is popped otherwise.
+
+No mutable access to the underlying table is provided, as pushing or popping it directly would leave the kinds of its
+levels out of step with its depth.
+*/
+#[derive(Debug, Clone)]
+pub struct Boundaries<K, T> {
+    /// The underlying symbol table
+    table: T,
+    /// The kind of each level, from the base level up, along with the keys captured by it
+    levels: Vec<Scope<K>>,
+}
+
+impl<K: Hash + Eq + Clone, T: SymbolMap<K>> Boundaries<K, T> {
+    /// Tag the levels of a symbol table with kinds, treating the base level as a module and every other as a block
+    #[inline]
+    pub fn new(table: T) -> Boundaries<K, T> {
+        let mut boundaries = Boundaries {
+            table,
+            levels: Vec::new(),
+        };
+        boundaries.levels.push(Scope::new(ScopeKind::Module));
+        boundaries.tag_levels(ScopeKind::Block);
+        boundaries
+    }
+    /// Get the underlying symbol table
+    #[inline]
+    pub fn table(&self) -> &T {
+        &self.table
+    }
+    /// Get the underlying symbol table, discarding the kinds of its levels
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.table
+    }
+    /// Get the kind of the level at a given depth, if there is one
+    #[inline]
+    pub fn kind(&self, depth: usize) -> Option<ScopeKind> {
+        self.levels.get(depth).map(|level| level.kind)
+    }
+    /// Get the kind of the current level
+    #[inline]
+    pub fn current_kind(&self) -> ScopeKind {
+        debug_assert_eq!(self.levels.len(), self.table.depth() + 1);
+        self.levels[self.table.depth()].kind
+    }
+    /// Get the keys captured so far by the level at a given depth, if there is one
+    #[inline]
+    pub fn captures(&self, depth: usize) -> Option<&HashSet<K>> {
+        self.levels.get(depth).map(|level| &level.captures)
+    }
+    /// Push a level of the given kind onto this symbol table
+    #[inline]
+    pub fn push_kind(&mut self, kind: ScopeKind) {
+        self.table.push();
+        self.tag_levels(kind);
+    }
+    /// Pop a level from this symbol table, returning its kind and captures, or failing if it is at depth 0
+    pub fn pop_scope(&mut self) -> Result<PoppedScope<K>, PopError> {
+        self.table.try_pop()?;
+        let Scope { kind, captures } = self
+            .levels
+            .pop()
+            .expect("a level above the base level exists");
+        Ok(PoppedScope { kind, captures })
+    }
+    /// Get the innermost definition of a key along with the boundaries crossed to reach it, recording it as captured by
+    /// each of them
+    pub fn get_crossing(&mut self, key: &K) -> Option<Crossing<'_, T::Value>> {
+        let (value, depth) = self.table.get_full(key)?;
+        let mut boundaries = Vec::new();
+        for (boundary, level) in self.levels.iter_mut().enumerate().skip(depth + 1) {
+            if level.kind.is_boundary() {
+                boundaries.push(boundary);
+                if !level.captures.contains(key) {
+                    level.captures.insert(key.clone());
+                }
+            }
+        }
+        Some(Crossing {
+            value,
+            depth,
+            boundaries,
+        })
+    }
+    /// Tag the untagged levels of the underlying table with a given kind
+    #[inline]
+    fn tag_levels(&mut self, kind: ScopeKind) {
+        self.levels
+            .resize_with(self.table.depth() + 1, || Scope::new(kind))
+    }
+}
+
+impl<K: Hash + Eq + Clone, T: SymbolMap<K>> SymbolMap<K> for Boundaries<K, T> {
+    type Value = T::Value;
+    type Defs<'a>
+        = T::Defs<'a>
+    where
+        Self: 'a;
+    type Iter<'a>
+        = T::Iter<'a>
+    where
+        Self: 'a;
+    type IterTop<'a>
+        = T::IterTop<'a>
+    where
+        Self: 'a;
+    #[inline]
+    fn insert(&mut self, key: K, value: T::Value) {
+        self.table.insert(key, value)
+    }
+    #[inline]
+    fn try_insert(&mut self, key: K, value: T::Value) -> Result<(), AlreadyDefined<'_, T::Value>> {
+        self.table.try_insert(key, value)
+    }
+    #[inline]
+    fn get<Q>(&self, key: &Q) -> Option<&T::Value>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        self.table.get(key)
+    }
+    #[inline]
+    fn get_full<Q>(&self, key: &Q) -> Option<(&T::Value, usize)>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        self.table.get_full(key)
+    }
+    #[inline]
+    fn get_defs<Q>(&self, key: &Q) -> T::Defs<'_>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        self.table.get_defs(key)
+    }
+    #[inline]
+    fn remove<Q>(&mut self, key: &Q) -> Option<T::Value>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        self.table.remove(key)
+    }
+    #[inline]
+    fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        self.table.contains_key(key)
+    }
+    #[inline]
+    fn iter(&self) -> T::Iter<'_> {
+        self.table.iter()
+    }
+    #[inline]
+    fn iter_top(&self) -> T::IterTop<'_> {
+        self.table.iter_top()
+    }
+    #[inline]
+    fn is_empty(&self) -> bool {
+        self.table.is_empty()
+    }
+    #[inline]
+    fn try_get_mut<Q>(&mut self, key: &Q) -> Option<&mut T::Value>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        self.table.try_get_mut(key)
+    }
+    #[inline]
+    fn push(&mut self) {
+        self.push_kind(ScopeKind::Block)
+    }
+    #[inline]
+    fn pop(&mut self) {
+        self.table.pop();
+        self.levels.truncate(self.table.depth() + 1);
+    }
+    #[inline]
+    fn jump(&mut self, depth: usize) {
+        self.table.jump(depth);
+        self.levels.truncate(depth + 1);
+        self.tag_levels(ScopeKind::Block);
+    }
+    #[inline]
+    fn depth(&self) -> usize {
+        self.table.depth()
+    }
+}
@@ -0,0 +1,134 @@
+/*!
+An object-safe companion to `SymbolMap`, allowing symbol tables to be chosen at runtime
+*/
+use super::*;
+
+/// A boxed iterator over the definitions of a key in a `DynSymbolMap`, along with their depths
+pub type DynDefs<'a, V> = Box<dyn Iterator<Item = (&'a V, usize)> + 'a>;
+
+/// A boxed iterator over bindings in a `DynSymbolMap`
+pub type DynIter<'a, K, V> = Box<dyn Iterator<Item = (&'a K, &'a V)> + 'a>;
+
+/**
+An object-safe version of `SymbolMap`, implemented for every `SymbolMap`.
+
+Keys are looked up by reference rather than by borrowed form, and iterators are boxed. Methods are prefixed with `dyn_`
+to avoid ambiguity with the corresponding `SymbolMap` methods.
+*/
+pub trait DynSymbolMap<K, V> {
+    /// Insert a key/value pair into this symbol table at the current level
+    fn dyn_insert(&mut self, key: K, value: V);
+    /// Try to insert a key/value pair into this symbol table at the current level, failing if the key is already
+    /// defined at the current level
+    fn dyn_try_insert(&mut self, key: K, value: V) -> Result<(), AlreadyDefined<'_, V>>;
+    /// Get the most recent definition of a key in this symbol table
+    fn dyn_get(&self, key: &K) -> Option<&V>;
+    /// Get the most recent definition of a key in this symbol table, along with the depth at which it was defined
+    fn dyn_get_full(&self, key: &K) -> Option<(&V, usize)>;
+    /// Get all definitions of a key in this symbol table, from most to least recent, along with their depths
+    fn dyn_get_defs<'a>(&'a self, key: &K) -> DynDefs<'a, V>
+    where
+        K: 'a;
+    /// Remove the definition of a key at the current level of this symbol table, returning it if there is any
+    fn dyn_remove(&mut self, key: &K) -> Option<V>;
+    /// Whether this symbol table contains this key
+    fn dyn_contains_key(&self, key: &K) -> bool;
+    /// Iterate over the bindings visible in this symbol table, in arbitrary order
+    fn dyn_iter(&self) -> DynIter<'_, K, V>;
+    /// Iterate over the bindings defined at the current level of this symbol table, in arbitrary order
+    fn dyn_iter_top(&self) -> DynIter<'_, K, V>;
+    /// Whether this symbol table is empty
+    fn dyn_is_empty(&self) -> bool;
+    /// Try to get a mutable reference to the definition of a key in the top level of this symbol table
+    fn dyn_try_get_mut(&mut self, key: &K) -> Option<&mut V>;
+    /// Push a level onto this symbol table
+    fn dyn_push(&mut self);
+    /// Pop a level from this symbol table, doing nothing if it is at depth 0
+    fn dyn_pop(&mut self);
+    /// Pop a level from this symbol table, failing if it is at depth 0
+    fn dyn_try_pop(&mut self) -> Result<(), PopError>;
+    /// Jump to a given depth, pushing or popping levels as necessary
+    fn dyn_jump(&mut self, depth: usize);
+    /// Pop up to `n` levels from this symbol table
+    fn dyn_popn(&mut self, n: usize);
+    /// Get the current depth of this symbol table
+    fn dyn_depth(&self) -> usize;
+}
+
+impl<K: Hash + Eq, S: SymbolMap<K>> DynSymbolMap<K, S::Value> for S {
+    #[inline]
+    fn dyn_insert(&mut self, key: K, value: S::Value) {
+        self.insert(key, value)
+    }
+    #[inline]
+    fn dyn_try_insert(
+        &mut self,
+        key: K,
+        value: S::Value,
+    ) -> Result<(), AlreadyDefined<'_, S::Value>> {
+        self.try_insert(key, value)
+    }
+    #[inline]
+    fn dyn_get(&self, key: &K) -> Option<&S::Value> {
+        self.get(key)
+    }
+    #[inline]
+    fn dyn_get_full(&self, key: &K) -> Option<(&S::Value, usize)> {
+        self.get_full(key)
+    }
+    #[inline]
+    fn dyn_get_defs<'a>(&'a self, key: &K) -> DynDefs<'a, S::Value>
+    where
+        K: 'a,
+    {
+        Box::new(self.get_defs(key))
+    }
+    #[inline]
+    fn dyn_remove(&mut self, key: &K) -> Option<S::Value> {
+        self.remove(key)
+    }
+    #[inline]
+    fn dyn_contains_key(&self, key: &K) -> bool {
+        self.contains_key(key)
+    }
+    #[inline]
+    fn dyn_iter(&self) -> DynIter<'_, K, S::Value> {
+        Box::new(self.iter())
+    }
+    #[inline]
+    fn dyn_iter_top(&self) -> DynIter<'_, K, S::Value> {
+        Box::new(self.iter_top())
+    }
+    #[inline]
+    fn dyn_is_empty(&self) -> bool {
+        self.is_empty()
+    }
+    #[inline]
+    fn dyn_try_get_mut(&mut self, key: &K) -> Option<&mut S::Value> {
+        self.try_get_mut(key)
+    }
+    #[inline]
+    fn dyn_push(&mut self) {
+        self.push()
+    }
+    #[inline]
+    fn dyn_pop(&mut self) {
+        self.pop()
+    }
+    #[inline]
+    fn dyn_try_pop(&mut self) -> Result<(), PopError> {
+        self.try_pop()
+    }
+    #[inline]
+    fn dyn_jump(&mut self, depth: usize) {
+        self.jump(depth)
+    }
+    #[inline]
+    fn dyn_popn(&mut self, n: usize) {
+        self.popn(n)
+    }
+    #[inline]
+    fn dyn_depth(&self) -> usize {
+        self.depth()
+    }
+}
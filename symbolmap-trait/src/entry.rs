@@ -0,0 +1,130 @@
+/*!
+Entries into the levels of a symbol table
+*/
+use super::*;
+
+/**
+A view into the definition of a single key in a symbol table, obtained from `EntrySymbolMap::entry`.
+
+An entry may be occupied by a definition at the current level, occupied by a definition at a previous level which would
+be shadowed by a new definition, or vacant. The `or_insert` family of methods and `and_modify` are keyed to the current
+level: a definition at a previous level is treated as absent, and is shadowed by the inserted value.
+*/
+pub enum Entry<'a, K, S: EntrySymbolMap<K> + ?Sized + 'a> {
+    /// An entry occupied by a definition, at the current level or a previous one
+    Occupied(S::Occupied<'a>),
+    /// A vacant entry
+    Vacant(S::Vacant<'a>),
+}
+
+impl<'a, K, S: EntrySymbolMap<K> + ?Sized + 'a> Entry<'a, K, S> {
+    /// Get the key of this entry
+    #[inline]
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(entry) => entry.key(),
+            Entry::Vacant(entry) => entry.key(),
+        }
+    }
+    /// Get the depth at which this entry is defined, if it is occupied
+    #[inline]
+    pub fn depth(&self) -> Option<usize> {
+        match self {
+            Entry::Occupied(entry) => Some(entry.depth()),
+            Entry::Vacant(_) => None,
+        }
+    }
+    /// Whether this entry is occupied by a definition at the current level
+    #[inline]
+    pub fn is_current(&self) -> bool {
+        match self {
+            Entry::Occupied(entry) => entry.is_current(),
+            Entry::Vacant(_) => false,
+        }
+    }
+    /// Get a mutable reference to the definition at the current level, inserting a value if there is none
+    #[inline]
+    pub fn or_insert(self, value: S::Value) -> &'a mut S::Value {
+        self.or_insert_with(|| value)
+    }
+    /// Get a mutable reference to the definition at the current level, inserting the result of a function if there
+    /// is none
+    #[inline]
+    pub fn or_insert_with<F>(self, f: F) -> &'a mut S::Value
+    where
+        F: FnOnce() -> S::Value,
+    {
+        match self {
+            Entry::Occupied(entry) if entry.is_current() => entry.into_mut(),
+            Entry::Occupied(entry) => entry.shadow(f()),
+            Entry::Vacant(entry) => entry.insert(f()),
+        }
+    }
+    /// Get a mutable reference to the definition at the current level, inserting the default value if there is none
+    #[inline]
+    pub fn or_default(self) -> &'a mut S::Value
+    where
+        S::Value: Default,
+    {
+        self.or_insert_with(Default::default)
+    }
+    /// Modify the definition at the current level, if there is any
+    #[inline]
+    pub fn and_modify<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce(&mut S::Value),
+    {
+        if let Entry::Occupied(entry) = &mut self {
+            if entry.is_current() {
+                f(entry.get_mut())
+            }
+        }
+        self
+    }
+}
+
+impl<'a, K, S: EntrySymbolMap<K> + ?Sized + 'a> Debug for Entry<'a, K, S>
+where
+    S::Occupied<'a>: Debug,
+    S::Vacant<'a>: Debug,
+{
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        match self {
+            Entry::Occupied(entry) => fmt.debug_tuple("Occupied").field(entry).finish(),
+            Entry::Vacant(entry) => fmt.debug_tuple("Vacant").field(entry).finish(),
+        }
+    }
+}
+
+/**
+An entry occupied by a definition in a symbol table
+*/
+pub trait OccupiedEntry<'a, K, V>: Sized {
+    /// Get the key of this entry
+    fn key(&self) -> &K;
+    /// Get a reference to the definition occupying this entry
+    fn get(&self) -> &V;
+    /// Get a mutable reference to the definition occupying this entry
+    fn get_mut(&mut self) -> &mut V;
+    /// Convert this entry into a mutable reference to the definition occupying it
+    fn into_mut(self) -> &'a mut V;
+    /// Replace the definition occupying this entry in place, returning the old value
+    fn insert(&mut self, value: V) -> V;
+    /// Get the depth at which the definition occupying this entry was defined
+    fn depth(&self) -> usize;
+    /// Whether the definition occupying this entry was defined at the current level
+    fn is_current(&self) -> bool;
+    /// Define this entry's key at the current level, shadowing the definition occupying this entry if it is at a
+    /// previous level and replacing it otherwise.
+    fn shadow(self, value: V) -> &'a mut V;
+}
+
+/**
+A vacant entry in a symbol table
+*/
+pub trait VacantEntry<'a, K, V>: Sized {
+    /// Get the key of this entry
+    fn key(&self) -> &K;
+    /// Define this entry's key at the current level
+    fn insert(self, value: V) -> &'a mut V;
+}
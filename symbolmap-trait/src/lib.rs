@@ -4,10 +4,32 @@ of symbol table crates, which were developed for use in the [`rain` programming
 */
 #![deny(missing_docs, unsafe_code, missing_debug_implementations)]
 use std::borrow::Borrow;
+use std::error::Error;
+use std::fmt::{self, Debug, Display, Formatter};
 use std::hash::Hash;
 
+pub mod boundary;
+pub mod dynamic;
+pub mod entry;
+pub mod namespace;
+pub mod prelude;
+pub mod resolve;
+pub mod scope;
 #[cfg(feature = "testing")]
 pub mod testing;
+pub mod usage;
+
+pub use boundary::{Boundaries, Crossing, PoppedScope, ScopeKind};
+pub use dynamic::DynSymbolMap;
+pub use entry::{Entry, OccupiedEntry, VacantEntry};
+pub use namespace::{Namespaced, NamespacedIter};
+pub use prelude::{Prelude, WithPrelude};
+pub use resolve::{
+    CachePolicy, CyclePolicy, ErrorPolicy, MissPolicy, ResolveError, ResolvePolicy, Resolver,
+    Resolving, WithResolver,
+};
+pub use scope::ScopeGuard;
+pub use usage::{Tracked, UsageDiagnostic};
 
 /**
 A trait for a symbol table which can be indexed by a given key.
@@ -17,10 +39,74 @@ Behaves like a stack of `HashMap`s.
 pub trait SymbolMap<K> {
     /// The value stored in this symbol table
     type Value;
-    /// Insert a key/value pair into this symbol table at the current level
+    /// An iterator over the definitions of a key in this symbol table, along with their depths
+    type Defs<'a>: Iterator<Item = (&'a Self::Value, usize)>
+    where
+        Self: 'a,
+        Self::Value: 'a;
+    /// An iterator over the bindings visible in this symbol table
+    type Iter<'a>: Iterator<Item = (&'a K, &'a Self::Value)>
+    where
+        Self: 'a,
+        K: 'a,
+        Self::Value: 'a;
+    /// An iterator over the bindings defined at the current level of this symbol table
+    type IterTop<'a>: Iterator<Item = (&'a K, &'a Self::Value)>
+    where
+        Self: 'a,
+        K: 'a,
+        Self::Value: 'a;
+    /// Insert a key/value pair into this symbol table at the current level, replacing any definition of the key made at
+    /// this level
     fn insert(&mut self, key: K, value: Self::Value);
+    /// Try to insert a key/value pair into this symbol table at the current level, failing if the key is already
+    /// defined at the current level.
+    ///
+    /// On failure, the rejected value is returned along with a reference to the existing definition.
+    #[inline]
+    fn try_insert(
+        &mut self,
+        key: K,
+        value: Self::Value,
+    ) -> Result<(), AlreadyDefined<'_, Self::Value>>
+    where
+        K: Hash + Eq,
+    {
+        let depth = self.depth();
+        if self.get_full(&key).map(|(_, d)| d == depth) == Some(true) {
+            let existing = self.get(&key).expect("key is defined");
+            return Err(AlreadyDefined { value, existing });
+        }
+        self.insert(key, value);
+        Ok(())
+    }
     /// Get the most recent definition of a key in this symbol table
     fn get<Q>(&self, key: &Q) -> Option<&Self::Value>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>;
+    /// Get the most recent definition of a key in this symbol table, along with the depth at which it was defined
+    #[inline]
+    fn get_full<Q>(&self, key: &Q) -> Option<(&Self::Value, usize)>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        self.get_defs(key).next()
+    }
+    /// Get all definitions of a key in this symbol table, from most to least recent, along with the depths at which
+    /// they were defined.
+    ///
+    /// Only the most recent definition at each depth is yielded, so depths are strictly decreasing. Overloads kept by an
+    /// `OverloadSymbolMap` are yielded by `get_all_merged` instead.
+    fn get_defs<Q>(&self, key: &Q) -> Self::Defs<'_>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>;
+    /// Remove the definition of a key at the current level of this symbol table, returning it if there is any.
+    ///
+    /// Any definition of the key at a previous level becomes visible again.
+    fn remove<Q>(&mut self, key: &Q) -> Option<Self::Value>
     where
         Q: ?Sized + Hash + Eq,
         K: Borrow<Q>;
@@ -33,6 +119,13 @@ pub trait SymbolMap<K> {
     {
         self.get(key).is_some()
     }
+    /// Iterate over the bindings visible in this symbol table, i.e. the most recent definition of each key, in
+    /// arbitrary order
+    fn iter(&self) -> Self::Iter<'_>;
+    /// Iterate over the bindings defined at the current level of this symbol table, in arbitrary order
+    ///
+    /// To iterate over every definition of a single key, including shadowed ones, use `get_defs`.
+    fn iter_top(&self) -> Self::IterTop<'_>;
     /// Whether this symbol table is empty
     fn is_empty(&self) -> bool;
     /// Try to get a mutable reference to the definition of a key in the top level of this symbol table
@@ -44,14 +137,89 @@ pub trait SymbolMap<K> {
         K: Borrow<Q>;
     /// Push a level onto this symbol table
     fn push(&mut self);
-    /// Pop a level from this symbol table
+    /// Pop a level from this symbol table, doing nothing if it is at depth 0
     ///
     /// Note that this is *not* guaranteed to drop all elements stored in the level!
     fn pop(&mut self);
+    /// Pop a level from this symbol table, failing if it is at depth 0
+    #[inline]
+    fn try_pop(&mut self) -> Result<(), PopError> {
+        if self.depth() == 0 {
+            return Err(PopError);
+        }
+        self.pop();
+        Ok(())
+    }
+    /// Jump to a given depth, pushing or popping levels as necessary
+    #[inline]
+    fn jump(&mut self, depth: usize) {
+        while self.depth() < depth {
+            self.push()
+        }
+        while self.depth() > depth {
+            self.pop()
+        }
+    }
+    /// Pop up to `n` levels from this symbol table
+    #[inline]
+    fn popn(&mut self, n: usize) {
+        self.jump(self.depth().saturating_sub(n))
+    }
+    /// Push a level onto this symbol table, returning a guard which pops it when dropped
+    #[inline]
+    fn scope(&mut self) -> ScopeGuard<'_, K, Self>
+    where
+        Self: Sized,
+    {
+        ScopeGuard::new(self)
+    }
+    /// Call a function on this symbol table with a new level pushed, popping it afterwards
+    #[inline]
+    fn with_scope<F, R>(&mut self, f: F) -> R
+    where
+        F: FnOnce(&mut Self) -> R,
+        Self: Sized,
+    {
+        let mut scope = self.scope();
+        f(&mut scope)
+    }
     /// Get the current depth of this symbol table
     fn depth(&self) -> usize;
 }
 
+/**
+An error returned when a key is already defined at the current level of a symbol table
+*/
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct AlreadyDefined<'a, V> {
+    /// The value which was not inserted
+    pub value: V,
+    /// The existing definition of the key
+    pub existing: &'a V,
+}
+
+impl<'a, V> Display for AlreadyDefined<'a, V> {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        write!(fmt, "key is already defined at the current level")
+    }
+}
+
+impl<'a, V: Debug> Error for AlreadyDefined<'a, V> {}
+
+/**
+An error returned when attempting to pop the base level of a symbol table
+*/
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct PopError;
+
+impl Display for PopError {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        write!(fmt, "cannot pop the base level of a symbol table")
+    }
+}
+
+impl Error for PopError {}
+
 /**
 A trait for a symbol table which in which entries may be infallibly mutated.
 */
@@ -67,6 +235,22 @@ pub trait MutSymbolMap<K>: SymbolMap<K> {
     }
 }
 
+/**
+A trait for a symbol table supporting in-place manipulation of the definition of a key via entries
+*/
+pub trait EntrySymbolMap<K>: SymbolMap<K> {
+    /// An entry occupied by a definition in this symbol table
+    type Occupied<'a>: OccupiedEntry<'a, K, Self::Value>
+    where
+        Self: 'a;
+    /// A vacant entry in this symbol table
+    type Vacant<'a>: VacantEntry<'a, K, Self::Value>
+    where
+        Self: 'a;
+    /// Get the entry for a key in this symbol table
+    fn entry(&mut self, key: K) -> Entry<'_, K, Self>;
+}
+
 /**
 A trait for a stack-like symbol table in which a reference to the previous layer may be obtained
 */
@@ -74,3 +258,30 @@ pub trait SymbolStack<K>: SymbolMap<K> {
     /// Get the previous layer of this symbol table
     fn prev(&self) -> Option<&Self>;
 }
+
+/**
+A trait for a symbol table supporting overloading, i.e. keeping several definitions of a key at the same level.
+
+`insert` replaces every definition of a key at the current level, including its overloads, with a single definition,
+whereas `insert_overload` keeps them. In either case, definitions at enclosing levels are only shadowed.
+*/
+pub trait OverloadSymbolMap<K>: SymbolMap<K> {
+    /// An iterator over overloaded definitions of a key, from most to least recent, along with their depths
+    type Overloads<'a>: Iterator<Item = (&'a Self::Value, usize)>
+    where
+        Self: 'a,
+        Self::Value: 'a;
+    /// Insert a definition of a key at the current level, keeping any earlier definitions at this level as overloads
+    fn insert_overload(&mut self, key: K, value: Self::Value);
+    /// Get every definition of a key at the innermost level which defines it, from most to least recent
+    fn get_all<Q>(&self, key: &Q) -> Self::Overloads<'_>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>;
+    /// Get every visible definition of a key, merging the overloads at each level which defines it, from most to least
+    /// recent
+    fn get_all_merged<Q>(&self, key: &Q) -> Self::Overloads<'_>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>;
+}
@@ -0,0 +1,252 @@
+/*!
+Several namespaces of bindings sharing a single stack of scopes
+*/
+use super::*;
+use std::hash::Hasher;
+use std::marker::PhantomData;
+
+/// A key in a given namespace, which any `(namespace, key)` pair may be borrowed as, so that lookups need not clone keys
+trait NamespacedKey<N, Q: ?Sized> {
+    /// The namespace of this key
+    fn namespace(&self) -> &N;
+    /// The key within its namespace
+    fn key(&self) -> &Q;
+}
+
+impl<N, K: Borrow<Q>, Q: ?Sized> NamespacedKey<N, Q> for (N, K) {
+    #[inline]
+    fn namespace(&self) -> &N {
+        &self.0
+    }
+    #[inline]
+    fn key(&self) -> &Q {
+        self.1.borrow()
+    }
+}
+
+impl<'a, N: 'a, K: Borrow<Q> + 'a, Q: ?Sized + 'a> Borrow<dyn NamespacedKey<N, Q> + 'a> for (N, K) {
+    #[inline]
+    fn borrow(&self) -> &(dyn NamespacedKey<N, Q> + 'a) {
+        self
+    }
+}
+
+impl<'a, N: Hash, Q: ?Sized + Hash> Hash for dyn NamespacedKey<N, Q> + 'a {
+    #[inline]
+    fn hash<H: Hasher>(&self, hasher: &mut H) {
+        // Hashes the same way as a tuple, since `K: Borrow<Q>` hashes the same way as `Q`
+        self.namespace().hash(hasher);
+        self.key().hash(hasher);
+    }
+}
+
+impl<'a, N: Eq, Q: ?Sized + Eq> PartialEq for dyn NamespacedKey<N, Q> + 'a {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.namespace() == other.namespace() && self.key() == other.key()
+    }
+}
+
+impl<'a, N: Eq, Q: ?Sized + Eq> Eq for dyn NamespacedKey<N, Q> + 'a {}
+
+/**
+A symbol table with a separate set of bindings in each of several namespaces, such as types, values and macros, which
+share a single stack of scopes.
+
+Namespaces may be any `Copy` type, typically a fieldless enum. Bindings are stored in a single `SymbolMap` keyed by
+`(namespace, key)` pairs, so pushing or popping a level applies to every namespace at once, and lookups do not need to
+clone the key.
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Namespaced<N, K, T> {
+    /// The underlying symbol table
+    table: T,
+    /// The namespaces and keys of the underlying table
+    keys: PhantomData<(N, K)>,
+}
+
+impl<N, K, T: Default> Default for Namespaced<N, K, T> {
+    #[inline]
+    fn default() -> Namespaced<N, K, T> {
+        Namespaced::new(T::default())
+    }
+}
+
+impl<N, K, T> Namespaced<N, K, T> {
+    /// Split the bindings of a symbol table keyed by `(namespace, key)` pairs into namespaces
+    #[inline]
+    pub fn new(table: T) -> Namespaced<N, K, T> {
+        Namespaced {
+            table,
+            keys: PhantomData,
+        }
+    }
+    /// Get the underlying symbol table, keyed by `(namespace, key)` pairs
+    #[inline]
+    pub fn table(&self) -> &T {
+        &self.table
+    }
+    /// Get the underlying symbol table, keyed by `(namespace, key)` pairs, mutably
+    #[inline]
+    pub fn table_mut(&mut self) -> &mut T {
+        &mut self.table
+    }
+    /// Get the underlying symbol table, keyed by `(namespace, key)` pairs
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.table
+    }
+}
+
+impl<N: Copy + Hash + Eq, K: Hash + Eq, T: SymbolMap<(N, K)>> Namespaced<N, K, T> {
+    /// Insert a definition of a key into a namespace at the current level
+    #[inline]
+    pub fn insert(&mut self, namespace: N, key: K, value: T::Value) {
+        self.table.insert((namespace, key), value)
+    }
+    /// Insert a definition of a key into a namespace, failing if the key is already defined there at the current level
+    #[inline]
+    pub fn try_insert(
+        &mut self,
+        namespace: N,
+        key: K,
+        value: T::Value,
+    ) -> Result<(), AlreadyDefined<'_, T::Value>> {
+        self.table.try_insert((namespace, key), value)
+    }
+    /// Get the most recent definition of a key in a namespace
+    #[inline]
+    pub fn get<Q>(&self, namespace: N, key: &Q) -> Option<&T::Value>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        self.table.get::<dyn NamespacedKey<N, Q>>(&(namespace, key))
+    }
+    /// Get the most recent definition of a key in a namespace, along with its depth
+    #[inline]
+    pub fn get_full<Q>(&self, namespace: N, key: &Q) -> Option<(&T::Value, usize)>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        self.table
+            .get_full::<dyn NamespacedKey<N, Q>>(&(namespace, key))
+    }
+    /// Iterate over the definitions of a key in a namespace, from most to least recent, along with their depths
+    #[inline]
+    pub fn get_defs<Q>(&self, namespace: N, key: &Q) -> T::Defs<'_>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        self.table
+            .get_defs::<dyn NamespacedKey<N, Q>>(&(namespace, key))
+    }
+    /// Check whether a key is defined in a namespace
+    #[inline]
+    pub fn contains_key<Q>(&self, namespace: N, key: &Q) -> bool
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        self.table
+            .contains_key::<dyn NamespacedKey<N, Q>>(&(namespace, key))
+    }
+    /// Try to get a mutable reference to the most recent definition of a key in a namespace, as by `try_get_mut`
+    #[inline]
+    pub fn try_get_mut<Q>(&mut self, namespace: N, key: &Q) -> Option<&mut T::Value>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        self.table
+            .try_get_mut::<dyn NamespacedKey<N, Q>>(&(namespace, key))
+    }
+    /// Remove the definition of a key in a namespace at the current level, returning it
+    #[inline]
+    pub fn remove<Q>(&mut self, namespace: N, key: &Q) -> Option<T::Value>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        self.table
+            .remove::<dyn NamespacedKey<N, Q>>(&(namespace, key))
+    }
+    /// Iterate over the bindings visible in every namespace, in arbitrary order
+    #[inline]
+    pub fn iter(&self) -> NamespacedIter<N, T::Iter<'_>> {
+        NamespacedIter {
+            iter: self.table.iter(),
+            namespace: None,
+        }
+    }
+    /// Iterate over the bindings visible in a namespace, in arbitrary order
+    #[inline]
+    pub fn iter_namespace(&self, namespace: N) -> NamespacedIter<N, T::Iter<'_>> {
+        NamespacedIter {
+            iter: self.table.iter(),
+            namespace: Some(namespace),
+        }
+    }
+    /// Whether no key is defined in any namespace
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.table.is_empty()
+    }
+    /// Push a level onto every namespace
+    #[inline]
+    pub fn push(&mut self) {
+        self.table.push()
+    }
+    /// Pop a level from every namespace, if the table is not at depth 0
+    #[inline]
+    pub fn pop(&mut self) {
+        self.table.pop()
+    }
+    /// Pop a level from every namespace, failing if the table is at depth 0
+    #[inline]
+    pub fn try_pop(&mut self) -> Result<(), PopError> {
+        self.table.try_pop()
+    }
+    /// Jump to a given depth in every namespace, pushing or popping levels as necessary
+    #[inline]
+    pub fn jump(&mut self, depth: usize) {
+        self.table.jump(depth)
+    }
+    /// Get the current depth of the table
+    #[inline]
+    pub fn depth(&self) -> usize {
+        self.table.depth()
+    }
+}
+
+/// An iterator over the bindings visible in a `Namespaced` symbol table, along with their namespaces
+#[derive(Debug, Clone)]
+pub struct NamespacedIter<N, I> {
+    /// The bindings visible in the underlying table
+    iter: I,
+    /// The namespace to iterate over, or `None` to iterate over every namespace
+    namespace: Option<N>,
+}
+
+impl<'a, N, K, V, I> Iterator for NamespacedIter<N, I>
+where
+    N: Copy + Eq + 'a,
+    K: 'a,
+    V: 'a,
+    I: Iterator<Item = (&'a (N, K), &'a V)>,
+{
+    type Item = (N, &'a K, &'a V);
+    #[inline]
+    fn next(&mut self) -> Option<(N, &'a K, &'a V)> {
+        let filter = self.namespace;
+        self.iter
+            .find(|((namespace, _), _)| filter.is_none_or(|filter| filter == *namespace))
+            .map(|((namespace, key), value)| (*namespace, key, value))
+    }
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.iter.size_hint().1)
+    }
+}
@@ -0,0 +1,377 @@
+/*!
+Frozen base scopes ("preludes") which may be shared beneath any number of symbol tables
+*/
+use super::*;
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::iter::FromIterator;
+use std::slice;
+use std::sync::Arc;
+
+/**
+A frozen set of bindings, used as a read-only base scope beneath a symbol table.
+
+Bindings are stored in a single allocation, sorted by hash, and looked up by binary search. A prelude is built once, and
+then shared between tables via `Arc`; see [`WithPrelude`]. When a key is given more than once, the last binding wins.
+*/
+#[derive(Debug, Clone)]
+pub struct Prelude<K, V, S = RandomState> {
+    /// The bindings of this prelude, along with the hashes of their keys, sorted by hash
+    entries: Box<[(u64, K, V)]>,
+    /// The hasher used to hash keys
+    hasher: S,
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher + Default> Default for Prelude<K, V, S> {
+    #[inline]
+    fn default() -> Prelude<K, V, S> {
+        Prelude {
+            entries: Box::new([]),
+            hasher: S::default(),
+        }
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher + Default> FromIterator<(K, V)> for Prelude<K, V, S> {
+    #[inline]
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        Prelude::with_hasher(iter, S::default())
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher> Prelude<K, V, S> {
+    /// Build a prelude from a set of bindings, using the provided hasher
+    pub fn with_hasher<I: IntoIterator<Item = (K, V)>>(iter: I, hasher: S) -> Prelude<K, V, S> {
+        let mut entries: Vec<_> = iter
+            .into_iter()
+            .map(|(key, value)| (hasher.hash_one(&key), key, value))
+            .collect();
+        // The sort is stable, so later bindings of a key stay after earlier ones
+        entries.sort_by_key(|(hash, _, _)| *hash);
+        let mut deduped: Vec<(u64, K, V)> = Vec::with_capacity(entries.len());
+        for entry in entries {
+            let run = deduped.partition_point(|(hash, _, _)| *hash < entry.0);
+            match deduped[run..]
+                .iter()
+                .position(|(_, key, _)| *key == entry.1)
+            {
+                Some(ix) => deduped[run + ix] = entry,
+                None => deduped.push(entry),
+            }
+        }
+        Prelude {
+            entries: deduped.into_boxed_slice(),
+            hasher,
+        }
+    }
+    /// Get the binding of a key in this prelude, if any
+    pub fn get_key_value<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        let hash = self.hasher.hash_one(key);
+        let start = self.entries.partition_point(|(h, _, _)| *h < hash);
+        self.entries[start..]
+            .iter()
+            .take_while(|(h, _, _)| *h == hash)
+            .find(|(_, k, _)| k.borrow() == key)
+            .map(|(_, key, value)| (key, value))
+    }
+    /// Get the value bound to a key in this prelude, if any
+    #[inline]
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        self.get_key_value(key).map(|(_, value)| value)
+    }
+    /// Whether this prelude binds a key
+    #[inline]
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        self.get_key_value(key).is_some()
+    }
+}
+
+impl<K, V, S> Prelude<K, V, S> {
+    /// Get the number of bindings in this prelude
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    /// Whether this prelude is empty
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+    /// Iterate over the bindings in this prelude, in arbitrary order
+    #[inline]
+    pub fn iter(&self) -> PreludeIter<'_, K, V> {
+        PreludeIter(self.entries.iter())
+    }
+    /// Get a reference to this prelude's hasher
+    #[inline]
+    pub fn hasher(&self) -> &S {
+        &self.hasher
+    }
+}
+
+/// An iterator over the bindings in a `Prelude`
+#[derive(Debug, Clone)]
+pub struct PreludeIter<'a, K, V>(slice::Iter<'a, (u64, K, V)>);
+
+impl<'a, K, V> Iterator for PreludeIter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+    #[inline]
+    fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        self.0.next().map(|(_, key, value)| (key, value))
+    }
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for PreludeIter<'a, K, V> {}
+
+/**
+A symbol table with a shared, read-only prelude beneath its base level.
+
+Lookups which find no definition in the table fall back to the prelude. The prelude is the level at depth 0, and the
+table's levels sit above it, so that a definition made at depth `d` of the table is reported at depth `d + 1`. The
+prelude can never be popped: its definitions may always be shadowed, even by `try_insert` at the table's base level, and
+are never removed, mutated, or yielded by `iter_top`.
+*/
+pub struct WithPrelude<K, T: SymbolMap<K>, S = RandomState> {
+    /// The shared prelude
+    prelude: Arc<Prelude<K, T::Value, S>>,
+    /// The symbol table on top of the prelude
+    table: T,
+}
+
+impl<K, T: SymbolMap<K>, S> WithPrelude<K, T, S> {
+    /// Attach a prelude beneath a symbol table
+    #[inline]
+    pub fn new(prelude: Arc<Prelude<K, T::Value, S>>, table: T) -> WithPrelude<K, T, S> {
+        WithPrelude { prelude, table }
+    }
+    /// Get the prelude beneath this symbol table
+    #[inline]
+    pub fn prelude(&self) -> &Arc<Prelude<K, T::Value, S>> {
+        &self.prelude
+    }
+    /// Get the symbol table on top of the prelude
+    #[inline]
+    pub fn table(&self) -> &T {
+        &self.table
+    }
+    /// Get the symbol table on top of the prelude, mutably
+    #[inline]
+    pub fn table_mut(&mut self) -> &mut T {
+        &mut self.table
+    }
+    /// Detach the prelude, returning it along with the symbol table on top of it
+    #[inline]
+    pub fn into_parts(self) -> (Arc<Prelude<K, T::Value, S>>, T) {
+        (self.prelude, self.table)
+    }
+}
+
+impl<K, T: SymbolMap<K> + Clone, S> Clone for WithPrelude<K, T, S> {
+    #[inline]
+    fn clone(&self) -> Self {
+        WithPrelude {
+            prelude: self.prelude.clone(),
+            table: self.table.clone(),
+        }
+    }
+}
+
+impl<K, T: SymbolMap<K> + Debug, S> Debug for WithPrelude<K, T, S> {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        fmt.debug_struct("WithPrelude")
+            .field("prelude", &self.prelude.len())
+            .field("table", &self.table)
+            .finish()
+    }
+}
+
+/// An iterator over the definitions of a key in a `WithPrelude`, from most to least recent, along with their depths
+pub struct Defs<'a, K: 'a, T: SymbolMap<K> + 'a> {
+    /// The definitions of the key in the table, at depths relative to the table's base level
+    table: T::Defs<'a>,
+    /// The definition of the key in the prelude, if not yet yielded
+    prelude: Option<&'a T::Value>,
+}
+
+impl<'a, K: 'a, T: SymbolMap<K> + 'a> Debug for Defs<'a, K, T> {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        fmt.debug_struct("Defs")
+            .field("prelude", &self.prelude.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<'a, K: 'a, T: SymbolMap<K> + 'a> Iterator for Defs<'a, K, T> {
+    type Item = (&'a T::Value, usize);
+    #[inline]
+    fn next(&mut self) -> Option<(&'a T::Value, usize)> {
+        match self.table.next() {
+            Some((value, depth)) => Some((value, depth + 1)),
+            None => self.prelude.take().map(|value| (value, 0)),
+        }
+    }
+}
+
+/// An iterator over the bindings visible in a `WithPrelude`
+pub struct Iter<'a, K: 'a, T: SymbolMap<K> + 'a> {
+    /// The symbol table on top of the prelude
+    table: &'a T,
+    /// The bindings visible in the table
+    bindings: T::Iter<'a>,
+    /// The bindings in the prelude, yielded once the table's bindings are exhausted
+    prelude: PreludeIter<'a, K, T::Value>,
+}
+
+impl<'a, K: 'a, T: SymbolMap<K> + 'a> Debug for Iter<'a, K, T> {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        fmt.debug_struct("Iter").finish_non_exhaustive()
+    }
+}
+
+impl<'a, K: Hash + Eq + 'a, T: SymbolMap<K> + 'a> Iterator for Iter<'a, K, T> {
+    type Item = (&'a K, &'a T::Value);
+    #[inline]
+    fn next(&mut self) -> Option<(&'a K, &'a T::Value)> {
+        if let Some(binding) = self.bindings.next() {
+            return Some(binding);
+        }
+        let table = self.table;
+        self.prelude.find(|(key, _)| !table.contains_key(*key))
+    }
+}
+
+impl<K: Hash + Eq, T: SymbolMap<K>, S: BuildHasher> SymbolMap<K> for WithPrelude<K, T, S> {
+    type Value = T::Value;
+    type Defs<'a>
+        = Defs<'a, K, T>
+    where
+        Self: 'a;
+    type Iter<'a>
+        = Iter<'a, K, T>
+    where
+        Self: 'a;
+    type IterTop<'a>
+        = T::IterTop<'a>
+    where
+        Self: 'a;
+    #[inline]
+    fn insert(&mut self, key: K, value: T::Value) {
+        self.table.insert(key, value)
+    }
+    #[inline]
+    fn try_insert(&mut self, key: K, value: T::Value) -> Result<(), AlreadyDefined<'_, T::Value>> {
+        self.table.try_insert(key, value)
+    }
+    #[inline]
+    fn get<Q>(&self, key: &Q) -> Option<&T::Value>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        self.table.get(key).or_else(|| self.prelude.get(key))
+    }
+    #[inline]
+    fn get_full<Q>(&self, key: &Q) -> Option<(&T::Value, usize)>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        match self.table.get_full(key) {
+            Some((value, depth)) => Some((value, depth + 1)),
+            None => self.prelude.get(key).map(|value| (value, 0)),
+        }
+    }
+    #[inline]
+    fn get_defs<Q>(&self, key: &Q) -> Defs<'_, K, T>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        Defs {
+            table: self.table.get_defs(key),
+            prelude: self.prelude.get(key),
+        }
+    }
+    #[inline]
+    fn remove<Q>(&mut self, key: &Q) -> Option<T::Value>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        self.table.remove(key)
+    }
+    #[inline]
+    fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        self.table.contains_key(key) || self.prelude.contains_key(key)
+    }
+    #[inline]
+    fn iter(&self) -> Iter<'_, K, T> {
+        Iter {
+            table: &self.table,
+            bindings: self.table.iter(),
+            prelude: self.prelude.iter(),
+        }
+    }
+    #[inline]
+    fn iter_top(&self) -> T::IterTop<'_> {
+        self.table.iter_top()
+    }
+    #[inline]
+    fn is_empty(&self) -> bool {
+        self.table.is_empty() && self.prelude.is_empty()
+    }
+    /// Try to get a mutable reference to the innermost definition of a key, failing if it is only defined in the
+    /// prelude
+    #[inline]
+    fn try_get_mut<Q>(&mut self, key: &Q) -> Option<&mut T::Value>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        self.table.try_get_mut(key)
+    }
+    #[inline]
+    fn push(&mut self) {
+        self.table.push()
+    }
+    /// Pop a level from the table, doing nothing if it is at its base level, since the prelude is never popped
+    #[inline]
+    fn pop(&mut self) {
+        self.table.pop()
+    }
+    /// Pop a level from the table, failing if it is at its base level, since the prelude is never popped
+    #[inline]
+    fn try_pop(&mut self) -> Result<(), PopError> {
+        self.table.try_pop()
+    }
+    /// Jump to a given depth, stopping at the table's base level, i.e. depth 1, since the prelude is never popped
+    #[inline]
+    fn jump(&mut self, depth: usize) {
+        self.table.jump(depth.saturating_sub(1))
+    }
+    /// Get the current depth of this symbol table, counting the prelude as depth 0
+    #[inline]
+    fn depth(&self) -> usize {
+        self.table.depth() + 1
+    }
+}
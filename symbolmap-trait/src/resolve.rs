@@ -0,0 +1,671 @@
+/*!
+Lazy resolution of keys which are not defined in a symbol table
+*/
+use super::*;
+use std::collections::{hash_map, HashMap, HashSet};
+
+/**
+A fallback consulted when a key is not defined at any level of a symbol table.
+
+Resolvers may look up other keys while resolving one, by calling [`Resolving::get_or_resolve`] on the table they are
+passed along with themselves; cycles of such lookups are handled as specified by the table's [`CyclePolicy`].
+
+Implemented for closures taking the key and the table; since a closure cannot pass itself on, closures can only resolve
+other keys using a different resolver.
+*/
+pub trait Resolver<K, T: SymbolMap<K>, E> {
+    /// Resolve a key, returning `None` if it is unbound
+    fn resolve(
+        &mut self,
+        key: &K,
+        table: &mut Resolving<K, T>,
+    ) -> Result<Option<T::Value>, ResolveError<K, E>>;
+}
+
+impl<K, T, E, F> Resolver<K, T, E> for F
+where
+    T: SymbolMap<K>,
+    F: FnMut(&K, &mut Resolving<K, T>) -> Result<Option<T::Value>, ResolveError<K, E>>,
+{
+    #[inline]
+    fn resolve(
+        &mut self,
+        key: &K,
+        table: &mut Resolving<K, T>,
+    ) -> Result<Option<T::Value>, ResolveError<K, E>> {
+        self(key, table)
+    }
+}
+
+/// Where resolved bindings are stored
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub enum CachePolicy {
+    /// Store resolved bindings beneath the base level of the table, where they survive popping any level
+    #[default]
+    Base,
+    /// Insert resolved bindings into the current level of the table, so that they are discarded when it is popped
+    Current,
+    /// Store resolved bindings beneath the table, discarding them when the level at the given depth, or the current level
+    /// if it is shallower, is popped. For example, a binding resolved inside a block may be cached at module scope.
+    Depth(usize),
+}
+
+/// How keys which a resolver finds to be unbound are handled
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub enum MissPolicy {
+    /// Resolve the key again on the next lookup
+    #[default]
+    Retry,
+    /// Remember that the key is unbound, where a resolved binding would be cached, without resolving it again
+    Cache,
+}
+
+/// How errors reported by a resolver are handled
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub enum ErrorPolicy {
+    /// Return the error to the caller
+    #[default]
+    Propagate,
+    /// Treat the key as unbound. The error is never cached, so the key is resolved again on the next lookup.
+    Unbound,
+}
+
+/// How a lookup of a key which is already being resolved is handled
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub enum CyclePolicy {
+    /// Fail with `ResolveError::Cycle`
+    #[default]
+    Error,
+    /// Treat the key as unbound for the nested lookup only
+    Unbound,
+}
+
+/// The policies governing the resolution of keys in a `Resolving` symbol table
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub struct ResolvePolicy {
+    /// Where resolved bindings are stored
+    pub cache: CachePolicy,
+    /// How unbound keys are handled
+    pub misses: MissPolicy,
+    /// How errors reported by the resolver are handled
+    pub errors: ErrorPolicy,
+    /// How cyclic lookups are handled
+    pub cycles: CyclePolicy,
+}
+
+/**
+An error resolving a key
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ResolveError<K, E> {
+    /// A key was looked up while it was being resolved. Contains the keys being resolved, starting from the one looked
+    /// up again, in the order their resolution began.
+    Cycle(Vec<K>),
+    /// The resolver failed
+    Resolver(E),
+}
+
+impl<K: Debug, E: Display> Display for ResolveError<K, E> {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        match self {
+            ResolveError::Cycle(keys) => write!(fmt, "cyclic resolution of {:?}", keys),
+            ResolveError::Resolver(err) => write!(fmt, "resolution failed: {}", err),
+        }
+    }
+}
+
+impl<K: Debug, E: Error + 'static> Error for ResolveError<K, E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ResolveError::Cycle(_) => None,
+            ResolveError::Resolver(err) => Some(err),
+        }
+    }
+}
+
+/**
+A symbol table in which keys not defined at any level may be resolved on demand.
+
+Lookups via [`get_or_resolve`](Resolving::get_or_resolve) fall back to a resolver, whose result is cached as specified by
+the table's [`ResolvePolicy`]. As a `SymbolMap`, a `Resolving` table never resolves keys, but bindings cached beneath
+the table are visible to every lookup, including `get_defs` and `iter`, which report them at the depth they are cached
+at, beneath any definition made at that depth. They are not definitions made at any level, so `iter_top` never yields
+them, but they are removed by `remove` at the depth they are cached at. They are not visible to the underlying table,
+which may be accessed directly via `table` and `table_mut`.
+
+Bindings cached at a given depth are discarded when its level is popped via `pop` or `jump`. Levels popped directly from
+the underlying table are only noticed on the next call to `get_or_resolve`, so a level which has been popped and pushed
+again in the meantime keeps the bindings cached at it.
+*/
+#[derive(Debug, Clone)]
+pub struct Resolving<K, T: SymbolMap<K>> {
+    /// The underlying symbol table
+    table: T,
+    /// The bindings resolved beneath the table, along with the depth they are cached at
+    resolved: HashMap<K, (T::Value, usize)>,
+    /// The keys found to be unbound, if misses are cached
+    unbound: HashSet<K>,
+    /// The keys resolved beneath a level above the base level, along with its depth
+    scoped: Vec<(usize, K)>,
+    /// The keys currently being resolved, in the order their resolution began
+    resolving: Vec<K>,
+    /// The policies governing resolution
+    policy: ResolvePolicy,
+}
+
+impl<K: Hash + Eq + Clone, T: SymbolMap<K>> Resolving<K, T> {
+    /// Resolve keys missing from a symbol table using the default policy
+    #[inline]
+    pub fn new(table: T) -> Resolving<K, T> {
+        Self::with_policy(table, ResolvePolicy::default())
+    }
+    /// Resolve keys missing from a symbol table using the given policy
+    #[inline]
+    pub fn with_policy(table: T, policy: ResolvePolicy) -> Resolving<K, T> {
+        Resolving {
+            table,
+            resolved: HashMap::new(),
+            unbound: HashSet::new(),
+            scoped: Vec::new(),
+            resolving: Vec::new(),
+            policy,
+        }
+    }
+    /// Get the policies governing resolution
+    #[inline]
+    pub fn policy(&self) -> ResolvePolicy {
+        self.policy
+    }
+    /// Get the underlying symbol table
+    #[inline]
+    pub fn table(&self) -> &T {
+        &self.table
+    }
+    /// Get the underlying symbol table, mutably
+    #[inline]
+    pub fn table_mut(&mut self) -> &mut T {
+        &mut self.table
+    }
+    /// Get the bindings resolved beneath the table, along with the depth they are cached at
+    #[inline]
+    pub fn resolved(&self) -> &HashMap<K, (T::Value, usize)> {
+        &self.resolved
+    }
+    /// Get the keys found to be unbound, if misses are cached
+    #[inline]
+    pub fn unbound(&self) -> &HashSet<K> {
+        &self.unbound
+    }
+    /// Forget every binding resolved beneath the table, along with every key found to be unbound
+    #[inline]
+    pub fn clear_resolved(&mut self) {
+        self.resolved.clear();
+        self.unbound.clear();
+        self.scoped.clear();
+    }
+    /// Discard the bindings cached at levels which have been popped from the table
+    fn discard_popped(&mut self) {
+        let depth = self.table.depth();
+        let resolved = &mut self.resolved;
+        let unbound = &mut self.unbound;
+        self.scoped.retain(|(scope, key)| {
+            if *scope > depth {
+                resolved.remove(key);
+                unbound.remove(key);
+            }
+            *scope <= depth
+        })
+    }
+    /// Get the depth of the level which a key resolved now is cached with
+    fn cache_depth(&self) -> usize {
+        match self.policy.cache {
+            CachePolicy::Base => 0,
+            CachePolicy::Current => self.table.depth(),
+            CachePolicy::Depth(depth) => depth.min(self.table.depth()),
+        }
+    }
+    /// Get the keys currently being resolved, in the order their resolution began
+    #[inline]
+    pub fn resolving(&self) -> &[K] {
+        &self.resolving
+    }
+    /// Get the underlying symbol table, discarding any bindings resolved beneath it
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.table
+    }
+    /// Get the definition of a key, resolving it with a resolver if it is not defined or already resolved
+    ///
+    /// Keys which the resolver finds to be unbound are resolved again on every lookup, unless misses are cached.
+    pub fn get_or_resolve<Q, E, R>(
+        &mut self,
+        key: &Q,
+        resolver: &mut R,
+    ) -> Result<Option<&T::Value>, ResolveError<K, E>>
+    where
+        Q: ?Sized + Hash + Eq + ToOwned<Owned = K>,
+        K: Borrow<Q>,
+        R: Resolver<K, T, E> + ?Sized,
+    {
+        self.discard_popped();
+        if self.get(key).is_some() {
+            return Ok(self.get(key));
+        }
+        if self.unbound.contains(key) {
+            return Ok(None);
+        }
+        if let Some(ix) = self
+            .resolving
+            .iter()
+            .position(|resolving| resolving.borrow() == key)
+        {
+            return match self.policy.cycles {
+                CyclePolicy::Error => Err(ResolveError::Cycle(self.resolving[ix..].to_vec())),
+                CyclePolicy::Unbound => Ok(None),
+            };
+        }
+        let key = key.to_owned();
+        self.resolving.push(key.clone());
+        let result = resolver.resolve(&key, self);
+        self.resolving.pop();
+        let value = match (result, self.policy.errors) {
+            (Ok(value), _) => value,
+            (Err(ResolveError::Resolver(_)), ErrorPolicy::Unbound) => return Ok(None),
+            (Err(err), _) => return Err(err),
+        };
+        if value.is_none() && self.policy.misses == MissPolicy::Retry {
+            return Ok(None);
+        }
+        let depth = self.cache_depth();
+        match value {
+            Some(value) if self.policy.cache == CachePolicy::Current => {
+                self.table.insert(key.clone(), value);
+                return Ok(self.get(key.borrow()));
+            }
+            Some(value) => {
+                self.resolved.insert(key.clone(), (value, depth));
+            }
+            None => {
+                self.unbound.insert(key.clone());
+            }
+        }
+        if depth > 0 {
+            self.scoped.push((depth, key.clone()));
+        }
+        Ok(self.get(key.borrow()))
+    }
+}
+
+/// An iterator over the definitions of a key in a `Resolving` table, from most to least recent, along with their depths
+pub struct Defs<'a, K: 'a, T: SymbolMap<K> + 'a> {
+    /// The definitions of the key in the table
+    table: T::Defs<'a>,
+    /// The depth of the last definition yielded from the table, if any
+    depth: Option<usize>,
+    /// The binding of the key cached beneath the table, along with its depth, if not yet yielded
+    resolved: Option<(&'a T::Value, usize)>,
+}
+
+impl<'a, K: 'a, T: SymbolMap<K> + 'a> Debug for Defs<'a, K, T> {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        fmt.debug_struct("Defs")
+            .field("depth", &self.depth)
+            .field("resolved", &self.resolved.map(|(_, depth)| depth))
+            .finish_non_exhaustive()
+    }
+}
+
+impl<'a, K: 'a, T: SymbolMap<K> + 'a> Iterator for Defs<'a, K, T> {
+    type Item = (&'a T::Value, usize);
+    #[inline]
+    fn next(&mut self) -> Option<(&'a T::Value, usize)> {
+        if let Some((value, depth)) = self.table.next() {
+            self.depth = Some(depth);
+            return Some((value, depth));
+        }
+        // A definition made at the depth a binding is cached at shadows it
+        let (value, depth) = self.resolved.take()?;
+        if self.depth == Some(depth) {
+            return None;
+        }
+        Some((value, depth))
+    }
+}
+
+/// An iterator over the bindings visible in a `Resolving` table
+pub struct Iter<'a, K: 'a, T: SymbolMap<K> + 'a> {
+    /// The underlying symbol table
+    table: &'a T,
+    /// The bindings visible in the table
+    bindings: T::Iter<'a>,
+    /// The bindings cached beneath the table, yielded once the table's bindings are exhausted
+    resolved: hash_map::Iter<'a, K, (T::Value, usize)>,
+}
+
+impl<'a, K: 'a, T: SymbolMap<K> + 'a> Debug for Iter<'a, K, T> {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        fmt.debug_struct("Iter").finish_non_exhaustive()
+    }
+}
+
+impl<'a, K: Hash + Eq + 'a, T: SymbolMap<K> + 'a> Iterator for Iter<'a, K, T> {
+    type Item = (&'a K, &'a T::Value);
+    #[inline]
+    fn next(&mut self) -> Option<(&'a K, &'a T::Value)> {
+        if let Some(binding) = self.bindings.next() {
+            return Some(binding);
+        }
+        let table = self.table;
+        self.resolved
+            .by_ref()
+            .find(|(key, _)| !table.contains_key(*key))
+            .map(|(key, (value, _))| (key, value))
+    }
+}
+
+impl<K: Hash + Eq + Clone, T: SymbolMap<K>> SymbolMap<K> for Resolving<K, T> {
+    type Value = T::Value;
+    type Defs<'a>
+        = Defs<'a, K, T>
+    where
+        Self: 'a;
+    type Iter<'a>
+        = Iter<'a, K, T>
+    where
+        Self: 'a;
+    type IterTop<'a>
+        = T::IterTop<'a>
+    where
+        Self: 'a;
+    #[inline]
+    fn insert(&mut self, key: K, value: T::Value) {
+        self.table.insert(key, value)
+    }
+    /// Get the definition of a key in the table, or failing that, its resolved binding, without resolving it
+    #[inline]
+    fn get<Q>(&self, key: &Q) -> Option<&T::Value>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        self.get_full(key).map(|(value, _)| value)
+    }
+    #[inline]
+    fn get_full<Q>(&self, key: &Q) -> Option<(&T::Value, usize)>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        self.table
+            .get_full(key)
+            .or_else(|| self.resolved.get(key).map(|(value, depth)| (value, *depth)))
+    }
+    #[inline]
+    fn get_defs<Q>(&self, key: &Q) -> Defs<'_, K, T>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        Defs {
+            table: self.table.get_defs(key),
+            depth: None,
+            resolved: self.resolved.get(key).map(|(value, depth)| (value, *depth)),
+        }
+    }
+    /// Remove the definition of a key at the current level, or failing that, its binding cached at the current depth
+    #[inline]
+    fn remove<Q>(&mut self, key: &Q) -> Option<T::Value>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        if let Some(value) = self.table.remove(key) {
+            return Some(value);
+        }
+        let depth = self.table.depth();
+        if self.resolved.get(key).map(|(_, cached)| *cached) != Some(depth) {
+            return None;
+        }
+        let (key, (value, _)) = self.resolved.remove_entry(key)?;
+        self.scoped.retain(|(_, scoped)| *scoped != key);
+        Some(value)
+    }
+    #[inline]
+    fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        self.table.contains_key(key) || self.resolved.contains_key(key)
+    }
+    #[inline]
+    fn iter(&self) -> Iter<'_, K, T> {
+        Iter {
+            table: &self.table,
+            bindings: self.table.iter(),
+            resolved: self.resolved.iter(),
+        }
+    }
+    #[inline]
+    fn iter_top(&self) -> T::IterTop<'_> {
+        self.table.iter_top()
+    }
+    #[inline]
+    fn is_empty(&self) -> bool {
+        self.table.is_empty() && self.resolved.is_empty()
+    }
+    #[inline]
+    fn try_get_mut<Q>(&mut self, key: &Q) -> Option<&mut T::Value>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        if self.table.contains_key(key) {
+            return self.table.try_get_mut(key);
+        }
+        self.resolved.get_mut(key).map(|(value, _)| value)
+    }
+    #[inline]
+    fn push(&mut self) {
+        self.table.push()
+    }
+    /// Pop a level from the table, discarding the bindings cached at it
+    #[inline]
+    fn pop(&mut self) {
+        self.table.pop();
+        self.discard_popped();
+    }
+    /// Jump to a given depth, discarding the bindings cached at each level popped
+    #[inline]
+    fn jump(&mut self, depth: usize) {
+        self.table.jump(depth);
+        self.discard_popped();
+    }
+    #[inline]
+    fn depth(&self) -> usize {
+        self.table.depth()
+    }
+}
+
+/**
+A symbol table with an attached resolver, consulted when a key is not defined at any level.
+
+See [`Resolving`] for details.
+*/
+pub struct WithResolver<K, T: SymbolMap<K>, R> {
+    /// The symbol table, along with any resolved bindings
+    table: Resolving<K, T>,
+    /// The attached resolver
+    resolver: R,
+}
+
+impl<K: Clone, T: SymbolMap<K> + Clone, R: Clone> Clone for WithResolver<K, T, R>
+where
+    T::Value: Clone,
+{
+    #[inline]
+    fn clone(&self) -> Self {
+        WithResolver {
+            table: self.table.clone(),
+            resolver: self.resolver.clone(),
+        }
+    }
+}
+
+impl<K: Debug, T: SymbolMap<K> + Debug, R> Debug for WithResolver<K, T, R>
+where
+    T::Value: Debug,
+{
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        fmt.debug_struct("WithResolver")
+            .field("table", &self.table)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<K: Hash + Eq + Clone, T: SymbolMap<K>, R> WithResolver<K, T, R> {
+    /// Attach a resolver to a symbol table, using the default policy
+    #[inline]
+    pub fn new(table: T, resolver: R) -> WithResolver<K, T, R> {
+        Self::with_policy(table, resolver, ResolvePolicy::default())
+    }
+    /// Attach a resolver to a symbol table, using the given policy
+    #[inline]
+    pub fn with_policy(table: T, resolver: R, policy: ResolvePolicy) -> WithResolver<K, T, R> {
+        WithResolver {
+            table: Resolving::with_policy(table, policy),
+            resolver,
+        }
+    }
+    /// Get the symbol table, along with any resolved bindings
+    #[inline]
+    pub fn resolving(&self) -> &Resolving<K, T> {
+        &self.table
+    }
+    /// Get the symbol table, along with any resolved bindings, mutably
+    #[inline]
+    pub fn resolving_mut(&mut self) -> &mut Resolving<K, T> {
+        &mut self.table
+    }
+    /// Get the attached resolver
+    #[inline]
+    pub fn resolver(&self) -> &R {
+        &self.resolver
+    }
+    /// Get the attached resolver, mutably
+    #[inline]
+    pub fn resolver_mut(&mut self) -> &mut R {
+        &mut self.resolver
+    }
+    /// Detach the resolver, returning it along with the symbol table
+    #[inline]
+    pub fn into_parts(self) -> (Resolving<K, T>, R) {
+        (self.table, self.resolver)
+    }
+    /// Get the definition of a key, resolving it with the attached resolver if it is not defined or already resolved
+    #[inline]
+    pub fn get_or_resolve<Q, E>(&mut self, key: &Q) -> Result<Option<&T::Value>, ResolveError<K, E>>
+    where
+        Q: ?Sized + Hash + Eq + ToOwned<Owned = K>,
+        K: Borrow<Q>,
+        R: Resolver<K, T, E>,
+    {
+        self.table.get_or_resolve(key, &mut self.resolver)
+    }
+}
+
+impl<K: Hash + Eq + Clone, T: SymbolMap<K>, R> SymbolMap<K> for WithResolver<K, T, R> {
+    type Value = T::Value;
+    type Defs<'a>
+        = Defs<'a, K, T>
+    where
+        Self: 'a;
+    type Iter<'a>
+        = Iter<'a, K, T>
+    where
+        Self: 'a;
+    type IterTop<'a>
+        = T::IterTop<'a>
+    where
+        Self: 'a;
+    #[inline]
+    fn insert(&mut self, key: K, value: T::Value) {
+        self.table.insert(key, value)
+    }
+    #[inline]
+    fn get<Q>(&self, key: &Q) -> Option<&T::Value>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        self.table.get(key)
+    }
+    #[inline]
+    fn get_full<Q>(&self, key: &Q) -> Option<(&T::Value, usize)>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        self.table.get_full(key)
+    }
+    #[inline]
+    fn get_defs<Q>(&self, key: &Q) -> Defs<'_, K, T>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        self.table.get_defs(key)
+    }
+    #[inline]
+    fn remove<Q>(&mut self, key: &Q) -> Option<T::Value>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        self.table.remove(key)
+    }
+    #[inline]
+    fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        self.table.contains_key(key)
+    }
+    #[inline]
+    fn iter(&self) -> Iter<'_, K, T> {
+        self.table.iter()
+    }
+    #[inline]
+    fn iter_top(&self) -> T::IterTop<'_> {
+        self.table.iter_top()
+    }
+    #[inline]
+    fn is_empty(&self) -> bool {
+        self.table.is_empty()
+    }
+    #[inline]
+    fn try_get_mut<Q>(&mut self, key: &Q) -> Option<&mut T::Value>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        self.table.try_get_mut(key)
+    }
+    #[inline]
+    fn push(&mut self) {
+        self.table.push()
+    }
+    #[inline]
+    fn pop(&mut self) {
+        self.table.pop()
+    }
+    #[inline]
+    fn jump(&mut self, depth: usize) {
+        self.table.jump(depth)
+    }
+    #[inline]
+    fn depth(&self) -> usize {
+        self.table.depth()
+    }
+}
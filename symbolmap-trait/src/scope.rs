@@ -0,0 +1,86 @@
+/*!
+RAII guards for the levels of a symbol table
+*/
+use super::*;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+
+/**
+A guard for a level pushed onto a symbol table, which pops the level when dropped.
+
+Dereferences to the underlying symbol table. In debug builds, asserts on drop that the levels pushed onto the table
+while the guard was alive have all been popped again, and that the guarded level itself has not been popped, unless the
+thread is already panicking. In release builds, the table is left at the depth it had when the guard was created, or
+left alone if it is already below that depth, rather than pushing fresh levels to replace the popped ones.
+*/
+pub struct ScopeGuard<'a, K, S: SymbolMap<K>> {
+    /// The guarded symbol table
+    table: &'a mut S,
+    /// The depth of the guarded level
+    depth: usize,
+    /// The key type of the guarded symbol table
+    key: PhantomData<fn(K)>,
+}
+
+impl<'a, K, S: SymbolMap<K>> ScopeGuard<'a, K, S> {
+    /// Push a new level onto a symbol table, returning a guard which pops it when dropped
+    #[inline]
+    pub fn new(table: &'a mut S) -> ScopeGuard<'a, K, S> {
+        table.push();
+        let depth = table.depth();
+        ScopeGuard {
+            table,
+            depth,
+            key: PhantomData,
+        }
+    }
+    /// Get the depth of the level guarded by this guard
+    #[inline]
+    pub fn guarded_depth(&self) -> usize {
+        self.depth
+    }
+}
+
+impl<'a, K, S: SymbolMap<K>> Deref for ScopeGuard<'a, K, S> {
+    type Target = S;
+    #[inline]
+    fn deref(&self) -> &S {
+        self.table
+    }
+}
+
+impl<'a, K, S: SymbolMap<K>> DerefMut for ScopeGuard<'a, K, S> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut S {
+        self.table
+    }
+}
+
+impl<'a, K, S: SymbolMap<K>> Drop for ScopeGuard<'a, K, S> {
+    #[inline]
+    fn drop(&mut self) {
+        let depth = self.table.depth();
+        if !std::thread::panicking() {
+            debug_assert!(
+                depth >= self.depth,
+                "the level guarded by a scope guard has been popped"
+            );
+            debug_assert_eq!(
+                depth, self.depth,
+                "unbalanced push/pop within a scope guard"
+            );
+        }
+        if depth >= self.depth {
+            self.table.jump(self.depth - 1)
+        }
+    }
+}
+
+impl<'a, K, S: SymbolMap<K> + Debug> Debug for ScopeGuard<'a, K, S> {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        fmt.debug_struct("ScopeGuard")
+            .field("table", &self.table)
+            .field("depth", &self.depth)
+            .finish()
+    }
+}
@@ -2,6 +2,14 @@
 Utility functions for testing `SymbolMap` implementations
 */
 use super::*;
+use std::collections::HashSet;
+use std::iter::FromIterator;
+
+mod model;
+pub use model::{
+    check_model, check_model_from, model_symbol_table_test, model_symbol_table_test_with_base, op,
+    ops, Model, Op, MODEL_KEYS,
+};
 
 /// A basic test of symbol table functionality, starting from an empty symbol table
 pub fn basic_symbol_table_test<S: SymbolMap<&'static str, Value = usize>>(symbols: &mut S) {
@@ -37,3 +45,883 @@ pub fn basic_symbol_table_test<S: SymbolMap<&'static str, Value = usize>>(symbol
     symbols.pop();
     assert!(!symbols.contains_key("z"))
 }
+
+/// A test of mutable access to the definitions in a symbol table, starting from an empty symbol table
+pub fn mutation_symbol_table_test<S: MutSymbolMap<&'static str, Value = usize>>(symbols: &mut S) {
+    assert!(symbols.is_empty());
+    assert_eq!(symbols.get_mut("x"), None);
+    symbols.insert("x", 4);
+    *symbols.get_mut("x").unwrap() = 5;
+    assert_eq!(symbols.get("x"), Some(&5));
+    symbols.insert("y", 7);
+    *symbols.get_mut("y").unwrap() += 1;
+    assert_eq!(symbols.get("x"), Some(&5));
+    assert_eq!(symbols.get("y"), Some(&8));
+    symbols.push();
+    *symbols.get_mut("y").unwrap() += 1;
+    assert_eq!(symbols.get("y"), Some(&9));
+    symbols.insert("x", 9);
+    *symbols.get_mut("x").unwrap() *= 2;
+    assert_eq!(symbols.get("x"), Some(&18));
+    assert_eq!(symbols.get_mut("z"), None);
+    symbols.insert("z", 1);
+    *symbols.get_mut("z").unwrap() = 2;
+    assert_eq!(symbols.get("z"), Some(&2));
+    symbols.pop();
+    assert_eq!(symbols.get("x"), Some(&5));
+    assert_eq!(symbols.get("y"), Some(&9));
+    assert_eq!(symbols.get_mut("z"), None);
+    *symbols.get_mut("x").unwrap() = 3;
+    assert_eq!(symbols.get("x"), Some(&3));
+}
+
+/// A test of depth-aware lookups in a symbol table, starting from an empty symbol table
+pub fn depth_symbol_table_test<S: SymbolMap<&'static str, Value = usize>>(symbols: &mut S) {
+    fn defs<S: SymbolMap<&'static str, Value = usize>>(
+        symbols: &S,
+        key: &str,
+    ) -> Vec<(usize, usize)> {
+        symbols.get_defs(key).map(|(v, d)| (*v, d)).collect()
+    }
+    assert!(symbols.is_empty());
+    assert_eq!(symbols.get_full("x"), None);
+    assert_eq!(defs(symbols, "x"), []);
+    symbols.insert("x", 1);
+    assert_eq!(symbols.get_full("x"), Some((&1, 0)));
+    assert_eq!(defs(symbols, "x"), [(1, 0)]);
+    symbols.push();
+    symbols.insert("y", 2);
+    symbols.push();
+    symbols.insert("x", 3);
+    assert_eq!(symbols.get_full("x"), Some((&3, 2)));
+    assert_eq!(symbols.get_full("y"), Some((&2, 1)));
+    assert_eq!(defs(symbols, "x"), [(3, 2), (1, 0)]);
+    assert_eq!(defs(symbols, "y"), [(2, 1)]);
+    symbols.push();
+    symbols.insert("y", 4);
+    symbols.insert("x", 5);
+    assert_eq!(defs(symbols, "x"), [(5, 3), (3, 2), (1, 0)]);
+    // Only the most recent definition at each depth is yielded
+    symbols.insert("x", 6);
+    assert_eq!(defs(symbols, "x"), [(6, 3), (3, 2), (1, 0)]);
+    assert_eq!(defs(symbols, "y"), [(4, 3), (2, 1)]);
+    assert_eq!(defs(symbols, "z"), []);
+    symbols.pop();
+    assert_eq!(defs(symbols, "x"), [(3, 2), (1, 0)]);
+    assert_eq!(defs(symbols, "y"), [(2, 1)]);
+    symbols.pop();
+    assert_eq!(symbols.get_full("x"), Some((&1, 0)));
+    assert_eq!(symbols.get_full("y"), Some((&2, 1)));
+    symbols.pop();
+    assert_eq!(defs(symbols, "x"), [(1, 0)]);
+    assert_eq!(symbols.get_full("y"), None);
+}
+
+/// A test of iteration over the bindings in a symbol table, starting from an empty symbol table
+pub fn iteration_symbol_table_test<S: SymbolMap<&'static str, Value = usize>>(symbols: &mut S) {
+    fn sorted<'a, I: Iterator<Item = (&'a &'static str, &'a usize)>>(
+        iter: I,
+    ) -> Vec<(&'static str, usize)> {
+        let mut bindings: Vec<_> = iter.map(|(k, v)| (*k, *v)).collect();
+        bindings.sort_unstable();
+        bindings
+    }
+    assert!(symbols.is_empty());
+    assert_eq!(sorted(symbols.iter()), []);
+    assert_eq!(sorted(symbols.iter_top()), []);
+    symbols.insert("x", 1);
+    symbols.insert("y", 2);
+    assert_eq!(sorted(symbols.iter()), [("x", 1), ("y", 2)]);
+    assert_eq!(sorted(symbols.iter_top()), [("x", 1), ("y", 2)]);
+    symbols.push();
+    assert_eq!(sorted(symbols.iter()), [("x", 1), ("y", 2)]);
+    assert_eq!(sorted(symbols.iter_top()), []);
+    symbols.insert("x", 3);
+    symbols.insert("z", 4);
+    assert_eq!(sorted(symbols.iter()), [("x", 3), ("y", 2), ("z", 4)]);
+    assert_eq!(sorted(symbols.iter_top()), [("x", 3), ("z", 4)]);
+    symbols.insert("x", 5);
+    symbols.insert("z", 6);
+    assert_eq!(sorted(symbols.iter()), [("x", 5), ("y", 2), ("z", 6)]);
+    assert_eq!(sorted(symbols.iter_top()), [("x", 5), ("z", 6)]);
+    symbols.push();
+    symbols.insert("y", 7);
+    assert_eq!(sorted(symbols.iter()), [("x", 5), ("y", 7), ("z", 6)]);
+    assert_eq!(sorted(symbols.iter_top()), [("y", 7)]);
+    symbols.pop();
+    assert_eq!(sorted(symbols.iter()), [("x", 5), ("y", 2), ("z", 6)]);
+    assert_eq!(sorted(symbols.iter_top()), [("x", 5), ("z", 6)]);
+    symbols.pop();
+    assert_eq!(sorted(symbols.iter()), [("x", 1), ("y", 2)]);
+    assert_eq!(sorted(symbols.iter_top()), [("x", 1), ("y", 2)]);
+    symbols.insert("x", 8);
+    assert_eq!(sorted(symbols.iter_top()), [("x", 8), ("y", 2)]);
+}
+
+/// A test of checked insertion into a symbol table, starting from an empty symbol table
+pub fn try_insert_symbol_table_test<S: SymbolMap<&'static str, Value = usize>>(symbols: &mut S) {
+    assert!(symbols.is_empty());
+    assert_eq!(symbols.try_insert("x", 1), Ok(()));
+    assert_eq!(
+        symbols.try_insert("x", 2),
+        Err(AlreadyDefined {
+            value: 2,
+            existing: &1
+        })
+    );
+    assert_eq!(symbols.get("x"), Some(&1));
+    symbols.push();
+    assert_eq!(symbols.try_insert("x", 3), Ok(()));
+    assert_eq!(symbols.get("x"), Some(&3));
+    assert_eq!(symbols.try_insert("y", 4), Ok(()));
+    assert_eq!(
+        symbols.try_insert("y", 5),
+        Err(AlreadyDefined {
+            value: 5,
+            existing: &4
+        })
+    );
+    assert_eq!(symbols.get_full("y"), Some((&4, 1)));
+    symbols.push();
+    assert_eq!(symbols.try_insert("y", 6), Ok(()));
+    symbols.pop();
+    assert_eq!(symbols.get("y"), Some(&4));
+    symbols.pop();
+    assert_eq!(symbols.get("x"), Some(&1));
+    assert_eq!(symbols.get("y"), None);
+    assert_eq!(
+        symbols.try_insert("x", 7),
+        Err(AlreadyDefined {
+            value: 7,
+            existing: &1
+        })
+    );
+    assert_eq!(symbols.try_insert("y", 8), Ok(()));
+    assert_eq!(symbols.get("y"), Some(&8));
+}
+
+/// A test of scope management in a symbol table, starting from an empty symbol table
+pub fn scope_symbol_table_test<S: SymbolMap<&'static str, Value = usize>>(symbols: &mut S) {
+    assert!(symbols.is_empty());
+    assert_eq!(symbols.depth(), 0);
+    assert_eq!(symbols.try_pop(), Err(PopError));
+    assert_eq!(symbols.depth(), 0);
+    symbols.insert("x", 1);
+    symbols.push();
+    symbols.insert("x", 2);
+    assert_eq!(symbols.try_pop(), Ok(()));
+    assert_eq!(symbols.depth(), 0);
+    assert_eq!(symbols.get("x"), Some(&1));
+    assert_eq!(symbols.try_pop(), Err(PopError));
+    assert_eq!(symbols.get("x"), Some(&1));
+    symbols.jump(3);
+    assert_eq!(symbols.depth(), 3);
+    symbols.insert("y", 3);
+    symbols.insert("x", 4);
+    symbols.jump(1);
+    assert_eq!(symbols.depth(), 1);
+    assert_eq!(symbols.get("x"), Some(&1));
+    assert_eq!(symbols.get("y"), None);
+    symbols.insert("z", 5);
+    symbols.push();
+    symbols.insert("x", 6);
+    symbols.push();
+    symbols.insert("y", 7);
+    symbols.insert("w", 8);
+    symbols.popn(2);
+    assert_eq!(symbols.depth(), 1);
+    assert_eq!(symbols.get("x"), Some(&1));
+    assert_eq!(symbols.get("y"), None);
+    assert_eq!(symbols.get("z"), Some(&5));
+    assert_eq!(symbols.get("w"), None);
+    symbols.insert("w", 9);
+    assert_eq!(symbols.get_full("w"), Some((&9, 1)));
+    symbols.jump(1);
+    assert_eq!(symbols.depth(), 1);
+    assert_eq!(symbols.get("w"), Some(&9));
+    symbols.popn(5);
+    assert_eq!(symbols.depth(), 0);
+    assert_eq!(symbols.get("x"), Some(&1));
+    assert_eq!(symbols.get("z"), None);
+    assert_eq!(symbols.get("w"), None);
+    symbols.insert("z", 10);
+    assert_eq!(symbols.get_full("z"), Some((&10, 0)));
+}
+
+/// A test of scope guards for a symbol table, starting from an empty symbol table
+pub fn scope_guard_symbol_table_test<S: SymbolMap<&'static str, Value = usize>>(symbols: &mut S) {
+    fn early_return<S: SymbolMap<&'static str, Value = usize>>(symbols: &mut S) -> Result<(), ()> {
+        let mut scope = symbols.scope();
+        scope.insert("z", 5);
+        Err(())?;
+        scope.insert("z", 6);
+        Ok(())
+    }
+    assert!(symbols.is_empty());
+    symbols.insert("x", 1);
+    {
+        let mut scope = symbols.scope();
+        assert_eq!(scope.depth(), 1);
+        assert_eq!(scope.guarded_depth(), 1);
+        scope.insert("x", 2);
+        assert_eq!(scope.get("x"), Some(&2));
+    }
+    assert_eq!(symbols.depth(), 0);
+    assert_eq!(symbols.get("x"), Some(&1));
+    let result = symbols.with_scope(|symbols| {
+        symbols.insert("y", 3);
+        let inner = symbols.with_scope(|symbols| {
+            symbols.insert("y", 4);
+            *symbols.get("y").unwrap()
+        });
+        inner + *symbols.get("y").unwrap()
+    });
+    assert_eq!(result, 7);
+    assert_eq!(symbols.depth(), 0);
+    assert_eq!(symbols.get("y"), None);
+    assert_eq!(early_return(symbols), Err(()));
+    assert_eq!(symbols.depth(), 0);
+    assert_eq!(symbols.get("z"), None);
+    assert_eq!(symbols.get("x"), Some(&1));
+}
+
+/// A test of removal of definitions from a symbol table, starting from an empty symbol table
+pub fn remove_symbol_table_test<S: SymbolMap<&'static str, Value = usize>>(symbols: &mut S) {
+    assert!(symbols.is_empty());
+    assert_eq!(symbols.remove("x"), None);
+    symbols.insert("x", 1);
+    symbols.push();
+    assert_eq!(symbols.remove("x"), None);
+    assert_eq!(symbols.get("x"), Some(&1));
+    symbols.insert("x", 2);
+    symbols.insert("x", 3);
+    assert_eq!(symbols.remove("x"), Some(3));
+    assert_eq!(symbols.get_full("x"), Some((&1, 0)));
+    assert_eq!(symbols.remove("x"), None);
+    symbols.insert("y", 4);
+    assert_eq!(symbols.remove("y"), Some(4));
+    assert_eq!(symbols.get("y"), None);
+    assert_eq!(symbols.iter_top().count(), 0);
+    symbols.insert("y", 5);
+    assert_eq!(symbols.get("y"), Some(&5));
+    assert_eq!(symbols.iter_top().collect::<Vec<_>>(), [(&"y", &5)]);
+    symbols.insert("z", 6);
+    symbols.insert("z", 7);
+    assert_eq!(symbols.remove("z"), Some(7));
+    assert_eq!(symbols.get("z"), None);
+    symbols.push();
+    symbols.insert("x", 8);
+    symbols.insert("z", 9);
+    symbols.pop();
+    assert_eq!(symbols.get("x"), Some(&1));
+    assert_eq!(symbols.get("z"), None);
+    symbols.insert("x", 10);
+    symbols.pop();
+    assert_eq!(symbols.get("x"), Some(&1));
+    assert_eq!(symbols.get("y"), None);
+    assert_eq!(symbols.get("z"), None);
+    assert!(!symbols.is_empty());
+    assert_eq!(symbols.remove("x"), Some(1));
+    assert_eq!(symbols.get("x"), None);
+    assert!(symbols.is_empty());
+}
+
+/// A test of the entry API of a symbol table, starting from an empty symbol table
+pub fn entry_symbol_table_test<S: EntrySymbolMap<&'static str, Value = usize>>(symbols: &mut S) {
+    assert!(symbols.is_empty());
+    match symbols.entry("x") {
+        Entry::Vacant(entry) => {
+            assert_eq!(entry.key(), &"x");
+            *entry.insert(1) += 1;
+        }
+        Entry::Occupied(_) => panic!("entry for x should be vacant"),
+    }
+    assert_eq!(symbols.get("x"), Some(&2));
+    let entry = symbols.entry("x");
+    assert_eq!(entry.key(), &"x");
+    assert_eq!(entry.depth(), Some(0));
+    assert!(entry.is_current());
+    *entry.or_insert(5) += 1;
+    assert_eq!(symbols.get("x"), Some(&3));
+    symbols.push();
+    let entry = symbols.entry("x");
+    assert_eq!(entry.depth(), Some(0));
+    assert!(!entry.is_current());
+    assert_eq!(*entry.or_insert(10), 10);
+    assert_eq!(symbols.get_full("x"), Some((&10, 1)));
+    assert_eq!(*symbols.entry("x").and_modify(|x| *x += 1).or_insert(0), 11);
+    assert_eq!(*symbols.entry("y").or_default(), 0);
+    assert_eq!(symbols.get_full("y"), Some((&0, 1)));
+    match symbols.entry("x") {
+        Entry::Occupied(mut entry) => {
+            assert_eq!(entry.key(), &"x");
+            assert_eq!(entry.get(), &11);
+            assert_eq!(entry.depth(), 1);
+            *entry.get_mut() = 12;
+            assert_eq!(entry.insert(13), 12);
+            assert_eq!(*entry.shadow(14), 14);
+        }
+        Entry::Vacant(_) => panic!("entry for x should be occupied"),
+    }
+    assert_eq!(symbols.get_full("x"), Some((&14, 1)));
+    symbols.push();
+    assert_eq!(
+        *symbols
+            .entry("y")
+            .and_modify(|_| panic!("y is not defined at the current level"))
+            .or_insert_with(|| 4),
+        4
+    );
+    assert_eq!(symbols.get_full("y"), Some((&4, 2)));
+    symbols.pop();
+    symbols.pop();
+    assert_eq!(symbols.get_full("x"), Some((&3, 0)));
+    assert_eq!(symbols.get("y"), None);
+    match symbols.entry("y") {
+        Entry::Vacant(entry) => assert_eq!(entry.key(), &"y"),
+        Entry::Occupied(_) => panic!("entry for y should be vacant"),
+    }
+    assert_eq!(symbols.get("y"), None);
+    assert_eq!(symbols.remove("x"), Some(3));
+    assert!(symbols.is_empty());
+}
+
+/// A test of a symbol table used as a trait object, starting from an empty symbol table
+pub fn dyn_symbol_table_test(symbols: &mut dyn DynSymbolMap<&'static str, usize>) {
+    fn sorted(iter: dynamic::DynIter<'_, &'static str, usize>) -> Vec<(&'static str, usize)> {
+        let mut bindings: Vec<_> = iter.map(|(k, v)| (*k, *v)).collect();
+        bindings.sort_unstable();
+        bindings
+    }
+    assert!(symbols.dyn_is_empty());
+    assert_eq!(symbols.dyn_get(&"x"), None);
+    symbols.dyn_insert("x", 1);
+    assert_eq!(symbols.dyn_try_insert("y", 2), Ok(()));
+    assert_eq!(
+        symbols.dyn_try_insert("y", 3),
+        Err(AlreadyDefined {
+            value: 3,
+            existing: &2
+        })
+    );
+    assert!(!symbols.dyn_is_empty());
+    assert!(symbols.dyn_contains_key(&"x"));
+    symbols.dyn_push();
+    symbols.dyn_insert("x", 4);
+    *symbols.dyn_try_get_mut(&"x").expect("x is defined") = 5;
+    assert_eq!(symbols.dyn_get(&"x"), Some(&5));
+    assert_eq!(symbols.dyn_get_full(&"y"), Some((&2, 0)));
+    assert_eq!(
+        symbols
+            .dyn_get_defs(&"x")
+            .map(|(v, d)| (*v, d))
+            .collect::<Vec<_>>(),
+        [(5, 1), (1, 0)]
+    );
+    assert_eq!(sorted(symbols.dyn_iter()), [("x", 5), ("y", 2)]);
+    assert_eq!(sorted(symbols.dyn_iter_top()), [("x", 5)]);
+    symbols.dyn_jump(3);
+    assert_eq!(symbols.dyn_depth(), 3);
+    symbols.dyn_insert("z", 6);
+    symbols.dyn_popn(2);
+    assert_eq!(symbols.dyn_depth(), 1);
+    assert_eq!(symbols.dyn_get(&"z"), None);
+    assert_eq!(symbols.dyn_remove(&"x"), Some(5));
+    assert_eq!(symbols.dyn_get(&"x"), Some(&1));
+    assert_eq!(symbols.dyn_try_pop(), Ok(()));
+    assert_eq!(symbols.dyn_try_pop(), Err(PopError));
+    symbols.dyn_pop();
+    assert_eq!(symbols.dyn_depth(), 0);
+    assert_eq!(sorted(symbols.dyn_iter()), [("x", 1), ("y", 2)]);
+}
+
+/// A test of the standard collection traits implemented by a symbol table
+pub fn collection_symbol_table_test<S>()
+where
+    S: SymbolMap<&'static str, Value = usize>
+        + FromIterator<(&'static str, usize)>
+        + Extend<(&'static str, usize)>
+        + IntoIterator<Item = (&'static str, usize)>
+        + for<'q> std::ops::Index<&'q str, Output = usize>,
+    for<'a> &'a S: IntoIterator<Item = (&'a &'static str, &'a usize)>,
+{
+    fn sorted<I: IntoIterator<Item = (&'static str, usize)>>(
+        iter: I,
+    ) -> Vec<(&'static str, usize)> {
+        let mut bindings: Vec<_> = iter.into_iter().collect();
+        bindings.sort_unstable();
+        bindings
+    }
+    let mut symbols: S = vec![("x", 1), ("y", 2), ("x", 3)].into_iter().collect();
+    assert_eq!(symbols.depth(), 0);
+    assert_eq!(symbols["x"], 3);
+    assert_eq!(symbols["y"], 2);
+    symbols.push();
+    symbols.extend(vec![("y", 4), ("z", 5)]);
+    assert_eq!(symbols.get_full("y"), Some((&4, 1)));
+    assert_eq!(symbols["z"], 5);
+    assert_eq!(
+        sorted(symbols.iter_top().map(|(k, v)| (*k, *v))),
+        [("y", 4), ("z", 5)]
+    );
+    assert_eq!(
+        sorted((&symbols).into_iter().map(|(k, v)| (*k, *v))),
+        [("x", 3), ("y", 4), ("z", 5)]
+    );
+    let mut count = 0;
+    for (_, value) in &symbols {
+        count += *value;
+    }
+    assert_eq!(count, 12);
+    symbols.pop();
+    assert_eq!(sorted(symbols), [("x", 3), ("y", 2)]);
+}
+
+/// A test of a symbol table with a shared prelude beneath it, starting from empty symbol tables created by `new`
+pub fn prelude_symbol_table_test<S, F>(new: F)
+where
+    S: SymbolMap<&'static str, Value = usize>,
+    F: Fn() -> S,
+{
+    fn sorted<'a, I: Iterator<Item = (&'a &'static str, &'a usize)>>(
+        iter: I,
+    ) -> Vec<(&'static str, usize)> {
+        let mut bindings: Vec<_> = iter.map(|(k, v)| (*k, *v)).collect();
+        bindings.sort_unstable();
+        bindings
+    }
+    let prelude: std::sync::Arc<Prelude<&'static str, usize>> = std::sync::Arc::new(
+        vec![("print", 100), ("len", 101), ("x", 0), ("x", 102)]
+            .into_iter()
+            .collect(),
+    );
+    assert_eq!(prelude.len(), 3);
+    let mut symbols = WithPrelude::new(prelude.clone(), new());
+    let other = WithPrelude::new(prelude.clone(), new());
+    assert!(std::sync::Arc::ptr_eq(symbols.prelude(), other.prelude()));
+    assert!(!symbols.is_empty());
+    assert_eq!(symbols.get("print"), Some(&100));
+    assert_eq!(symbols.get_full("x"), Some((&102, 0)));
+    assert!(symbols.contains_key("len"));
+    assert!(!symbols.contains_key("y"));
+    // The prelude is at depth 0, so the base level of the table is at depth 1
+    assert_eq!(symbols.depth(), 1);
+    assert_eq!(symbols.try_pop(), Err(PopError));
+    symbols.jump(0);
+    assert_eq!(symbols.depth(), 1);
+    symbols.insert("x", 1);
+    assert_eq!(symbols.get_full("x"), Some((&1, 1)));
+    assert_eq!(
+        symbols
+            .get_defs("x")
+            .map(|(v, d)| (*v, d))
+            .collect::<Vec<_>>(),
+        [(1, 1), (102, 0)]
+    );
+    assert_eq!(symbols.try_insert("print", 5), Ok(()));
+    assert_eq!(sorted(symbols.iter_top()), [("print", 5), ("x", 1)]);
+    symbols.push();
+    assert_eq!(symbols.depth(), 2);
+    symbols.insert("len", 7);
+    assert_eq!(symbols.get_full("len"), Some((&7, 2)));
+    assert_eq!(sorted(symbols.iter_top()), [("len", 7)]);
+    assert_eq!(sorted(symbols.iter()), [("len", 7), ("print", 5), ("x", 1)]);
+    assert_eq!(symbols.remove("len"), Some(7));
+    assert_eq!(symbols.get("len"), Some(&101));
+    assert_eq!(symbols.try_get_mut("len"), None);
+    assert_eq!(symbols.remove("len"), None);
+    assert_eq!(symbols.try_pop(), Ok(()));
+    assert_eq!(symbols.remove("print"), Some(5));
+    assert_eq!(symbols.get("print"), Some(&100));
+    assert_eq!(
+        sorted(symbols.iter()),
+        [("len", 101), ("print", 100), ("x", 1)]
+    );
+    assert_eq!(other.get("x"), Some(&102));
+    assert_eq!(sorted(other.iter_top()), []);
+}
+
+/// A resolver for testing, resolving `a` to 1, `b` to one more than `a`, `c` and `d` to one more than each other, and
+/// failing on `bad`
+#[derive(Debug, Default)]
+struct TestResolver {
+    /// The number of times a key has been resolved
+    calls: usize,
+}
+
+impl<S: SymbolMap<&'static str, Value = usize>> Resolver<&'static str, S, &'static str>
+    for TestResolver
+{
+    fn resolve(
+        &mut self,
+        key: &&'static str,
+        table: &mut Resolving<&'static str, S>,
+    ) -> Result<Option<usize>, ResolveError<&'static str, &'static str>> {
+        self.calls += 1;
+        let dependency = match *key {
+            "a" => return Ok(Some(1)),
+            "b" => "a",
+            "c" => "d",
+            "d" => "c",
+            "bad" => return Err(ResolveError::Resolver("bad")),
+            _ => return Ok(None),
+        };
+        Ok(table
+            .get_or_resolve(&dependency, self)?
+            .map(|value| value + 1))
+    }
+}
+
+/// A test of lazily resolving keys missing from a symbol table, starting from empty symbol tables created by `new`
+pub fn resolver_symbol_table_test<S, F>(new: F)
+where
+    S: SymbolMap<&'static str, Value = usize>,
+    F: Fn() -> S,
+{
+    fn sorted<'a, I: Iterator<Item = (&'a &'static str, &'a usize)>>(
+        iter: I,
+    ) -> Vec<(&'static str, usize)> {
+        let mut bindings: Vec<_> = iter.map(|(k, v)| (*k, *v)).collect();
+        bindings.sort_unstable();
+        bindings
+    }
+    let mut symbols = WithResolver::new(new(), TestResolver::default());
+    symbols.resolving_mut().table_mut().insert("x", 7);
+    assert_eq!(symbols.get_or_resolve(&"x"), Ok(Some(&7)));
+    assert_eq!(symbols.resolver().calls, 0);
+    assert_eq!(symbols.get_or_resolve(&"b"), Ok(Some(&2)));
+    assert_eq!(symbols.resolver().calls, 2);
+    assert_eq!(symbols.resolving().resolved().len(), 2);
+    assert_eq!(symbols.resolving().table().get("a"), None);
+    symbols.resolving_mut().table_mut().push();
+    assert_eq!(symbols.get_or_resolve(&"a"), Ok(Some(&1)));
+    symbols.resolving_mut().table_mut().insert("a", 5);
+    assert_eq!(symbols.get_or_resolve(&"a"), Ok(Some(&5)));
+    symbols.resolving_mut().table_mut().pop();
+    assert_eq!(symbols.resolving().get(&"a"), Some(&1));
+    assert_eq!(symbols.get_full("a"), Some((&1, 0)));
+    symbols.insert("a", 6);
+    assert_eq!(symbols.get_defs("a").collect::<Vec<_>>(), [(&6, 0)]);
+    assert_eq!(symbols.remove("a"), Some(6));
+    assert_eq!(symbols.get_defs("a").collect::<Vec<_>>(), [(&1, 0)]);
+    assert_eq!(sorted(symbols.iter()), [("a", 1), ("b", 2), ("x", 7)]);
+    assert_eq!(sorted(symbols.iter_top()), [("x", 7)]);
+    assert!(!symbols.is_empty());
+    assert_eq!(symbols.resolver().calls, 2);
+    assert_eq!(symbols.get_or_resolve(&"missing"), Ok(None));
+    assert_eq!(symbols.get_or_resolve(&"missing"), Ok(None));
+    assert_eq!(symbols.resolver().calls, 4);
+    assert_eq!(
+        symbols.get_or_resolve(&"bad"),
+        Err(ResolveError::Resolver("bad"))
+    );
+    assert_eq!(
+        symbols.get_or_resolve(&"c"),
+        Err(ResolveError::Cycle(vec!["c", "d"]))
+    );
+    assert_eq!(symbols.resolving().resolving(), &[] as &[&str]);
+
+    let policy = ResolvePolicy {
+        cache: CachePolicy::Current,
+        misses: MissPolicy::Retry,
+        errors: ErrorPolicy::Unbound,
+        cycles: CyclePolicy::Unbound,
+    };
+    let mut symbols = WithResolver::with_policy(new(), TestResolver::default(), policy);
+    symbols.push();
+    assert_eq!(symbols.get_or_resolve(&"b"), Ok(Some(&2)));
+    assert_eq!(symbols.resolving().table().get_full("a"), Some((&1, 1)));
+    assert_eq!(symbols.resolving().resolved().len(), 0);
+    assert_eq!(symbols.get_defs("b").collect::<Vec<_>>(), [(&2, 1)]);
+    assert_eq!(sorted(symbols.iter()), [("a", 1), ("b", 2)]);
+    symbols.pop();
+    assert_eq!(symbols.get("b"), None);
+    assert_eq!(symbols.get_defs("a").count(), 0);
+    assert!(symbols.is_empty());
+    assert_eq!(symbols.get_or_resolve(&"bad"), Ok(None));
+    assert_eq!(symbols.get_or_resolve(&"c"), Ok(None));
+    assert_eq!(symbols.resolving().table().get("d"), None);
+
+    let policy = ResolvePolicy {
+        cache: CachePolicy::Depth(1),
+        ..ResolvePolicy::default()
+    };
+    let mut symbols = WithResolver::with_policy(new(), TestResolver::default(), policy);
+    symbols.push();
+    symbols.push();
+    assert_eq!(symbols.get_or_resolve(&"a"), Ok(Some(&1)));
+    assert_eq!(symbols.resolving().table().get("a"), None);
+    assert_eq!(symbols.get_defs("a").collect::<Vec<_>>(), [(&1, 1)]);
+    assert_eq!(sorted(symbols.iter()), [("a", 1)]);
+    assert_eq!(sorted(symbols.iter_top()), []);
+    symbols.pop();
+    assert_eq!(symbols.get_full("a"), Some((&1, 1)));
+    symbols.pop();
+    assert_eq!(symbols.get("a"), None);
+    assert_eq!(sorted(symbols.iter()), []);
+    assert!(symbols.is_empty());
+    symbols.resolving_mut().push();
+    assert_eq!(symbols.get_or_resolve(&"b"), Ok(Some(&2)));
+    assert_eq!(symbols.resolver().calls, 3);
+    symbols.resolving_mut().table_mut().pop();
+    assert_eq!(symbols.resolving().get(&"b"), Some(&2));
+    assert_eq!(symbols.get_or_resolve(&"missing"), Ok(None));
+    assert_eq!(symbols.resolving().resolved().len(), 0);
+    assert_eq!(symbols.get_or_resolve(&"b"), Ok(Some(&2)));
+    assert_eq!(symbols.resolver().calls, 6);
+    symbols.resolving_mut().jump(2);
+    assert_eq!(
+        symbols.get_or_resolve(&"c"),
+        Err(ResolveError::Cycle(vec!["c", "d"]))
+    );
+    symbols.resolving_mut().jump(0);
+    assert_eq!(symbols.resolving().resolved().len(), 2);
+
+    let policy = ResolvePolicy {
+        cache: CachePolicy::Current,
+        misses: MissPolicy::Cache,
+        ..ResolvePolicy::default()
+    };
+    let mut symbols = WithResolver::with_policy(new(), TestResolver::default(), policy);
+    assert_eq!(symbols.get_or_resolve(&"missing"), Ok(None));
+    assert_eq!(symbols.get_or_resolve(&"missing"), Ok(None));
+    assert_eq!(symbols.resolver().calls, 1);
+    symbols.resolving_mut().push();
+    assert_eq!(symbols.get_or_resolve(&"unknown"), Ok(None));
+    assert_eq!(symbols.get_or_resolve(&"unknown"), Ok(None));
+    assert_eq!(
+        symbols.get_or_resolve(&"bad"),
+        Err(ResolveError::Resolver("bad"))
+    );
+    assert_eq!(symbols.resolver().calls, 3);
+    assert_eq!(symbols.resolving().unbound().len(), 2);
+    symbols.resolving_mut().pop();
+    assert_eq!(symbols.get_or_resolve(&"unknown"), Ok(None));
+    assert_eq!(symbols.get_or_resolve(&"missing"), Ok(None));
+    assert_eq!(symbols.resolver().calls, 4);
+    symbols.resolving_mut().table_mut().insert("missing", 3);
+    assert_eq!(symbols.get_or_resolve(&"missing"), Ok(Some(&3)));
+}
+
+/// A test of tracking the uses of bindings, starting from an empty symbol table
+pub fn usage_symbol_table_test<S: SymbolMap<&'static str, Value = usize>>(symbols: S) {
+    let mut symbols = Tracked::new(symbols);
+    symbols.insert("x", 1);
+    symbols.insert("y", 2);
+    assert_eq!(symbols.get("x"), Some(&1));
+    assert_eq!(symbols.uses("x"), Some(1));
+    assert_eq!(symbols.uses("y"), Some(0));
+    assert!(symbols.contains_key("y"));
+    assert_eq!(symbols.get_defs("y").count(), 1);
+    assert_eq!(symbols.uses("y"), Some(0));
+    symbols.insert("y", 3);
+    assert_eq!(
+        symbols.take_diagnostics(),
+        vec![UsageDiagnostic::Shadowed {
+            key: "y",
+            depth: 0,
+            shadowed_depth: 0,
+            uses: 0
+        }]
+    );
+    symbols.push();
+    symbols.insert("x", 4);
+    symbols.insert("z", 5);
+    *symbols.try_get_mut("z").unwrap() += 1;
+    assert_eq!(symbols.get_full("z"), Some((&6, 1)));
+    assert_eq!(symbols.uses("z"), Some(2));
+    assert!(symbols.try_insert("w", 7).is_ok());
+    assert!(symbols.try_insert("w", 8).is_err());
+    assert_eq!(
+        symbols.take_diagnostics(),
+        vec![UsageDiagnostic::Shadowed {
+            key: "x",
+            depth: 1,
+            shadowed_depth: 0,
+            uses: 1
+        }]
+    );
+    symbols.push();
+    symbols.insert("v", 9);
+    assert_eq!(symbols.remove("v"), Some(9));
+    symbols.jump(0);
+    let mut diagnostics = symbols.take_diagnostics();
+    diagnostics.sort_by_key(|diagnostic| match diagnostic {
+        UsageDiagnostic::Unused { key, .. } | UsageDiagnostic::Shadowed { key, .. } => *key,
+    });
+    assert_eq!(
+        diagnostics,
+        vec![
+            UsageDiagnostic::Unused { key: "v", depth: 2 },
+            UsageDiagnostic::Unused { key: "w", depth: 1 },
+            UsageDiagnostic::Unused { key: "x", depth: 1 },
+        ]
+    );
+    assert_eq!(symbols.get("x"), Some(&1));
+    assert_eq!(symbols.uses("x"), Some(2));
+    let unused: Vec<_> = symbols.unused().collect();
+    assert_eq!(unused, vec![(&"y", 0)]);
+    symbols.pop();
+    assert!(symbols.diagnostics().is_empty());
+    assert_eq!(symbols.into_inner().get("y"), Some(&3));
+}
+
+/// A test of tagging levels with scope kinds and analysing captures, starting from an empty symbol table
+pub fn boundary_symbol_table_test<S: SymbolMap<&'static str, Value = usize>>(symbols: S) {
+    let mut symbols = Boundaries::new(symbols);
+    assert_eq!(symbols.current_kind(), ScopeKind::Module);
+    symbols.insert("x", 1);
+    symbols.insert("y", 2);
+    symbols.push_kind(ScopeKind::Function);
+    symbols.insert("z", 3);
+    symbols.push_kind(ScopeKind::Loop);
+    symbols.push_kind(ScopeKind::Function);
+    symbols.push();
+    assert_eq!(symbols.current_kind(), ScopeKind::Block);
+    assert_eq!(symbols.kind(3), Some(ScopeKind::Function));
+    assert_eq!(symbols.kind(5), None);
+    assert_eq!(
+        symbols.get_crossing(&"x"),
+        Some(Crossing {
+            value: &1,
+            depth: 0,
+            boundaries: vec![1, 3]
+        })
+    );
+    assert_eq!(
+        symbols
+            .get_crossing(&"z")
+            .map(|crossing| crossing.boundaries),
+        Some(vec![3])
+    );
+    assert_eq!(symbols.get_crossing(&"w"), None);
+    symbols.insert("y", 4);
+    assert_eq!(
+        symbols
+            .get_crossing(&"y")
+            .map(|crossing| crossing.boundaries),
+        Some(vec![])
+    );
+    assert_eq!(
+        symbols.pop_scope(),
+        Ok(PoppedScope {
+            kind: ScopeKind::Block,
+            captures: HashSet::new()
+        })
+    );
+    assert_eq!(symbols.get("y"), Some(&2));
+    let inner = symbols.pop_scope().unwrap();
+    assert_eq!(inner.kind, ScopeKind::Function);
+    assert_eq!(inner.captures, vec!["x", "z"].into_iter().collect());
+    assert_eq!(symbols.captures(1), Some(&vec!["x"].into_iter().collect()));
+    symbols.jump(3);
+    assert_eq!(symbols.kind(3), Some(ScopeKind::Block));
+    assert_eq!(symbols.get_crossing(&"y").unwrap().boundaries, vec![1]);
+    symbols.jump(1);
+    let outer = symbols.pop_scope().unwrap();
+    assert_eq!(outer.kind, ScopeKind::Function);
+    assert_eq!(outer.captures, vec!["x", "y"].into_iter().collect());
+    assert_eq!(symbols.pop_scope(), Err(PopError));
+    assert_eq!(symbols.into_inner().get("x"), Some(&1));
+}
+
+/// A test of splitting bindings into namespaces sharing a single stack of scopes, starting from an empty symbol table
+pub fn namespaced_symbol_table_test<S>(symbols: S)
+where
+    S: SymbolMap<(&'static str, &'static str), Value = usize>,
+{
+    let mut symbols = Namespaced::new(symbols);
+    symbols.insert("type", "x", 1);
+    symbols.insert("value", "x", 2);
+    assert_eq!(symbols.get("type", "x"), Some(&1));
+    assert_eq!(symbols.get("value", "x"), Some(&2));
+    assert_eq!(symbols.get("macro", "x"), None);
+    symbols.push();
+    symbols.insert("value", "x", 3);
+    symbols.insert("macro", "m", 4);
+    assert!(symbols.try_insert("macro", "m", 5).is_err());
+    assert_eq!(symbols.get_full("type", "x"), Some((&1, 0)));
+    assert_eq!(symbols.get_full("value", "x"), Some((&3, 1)));
+    assert_eq!(
+        symbols
+            .get_defs("value", "x")
+            .map(|(value, depth)| (*value, depth))
+            .collect::<Vec<_>>(),
+        [(3, 1), (2, 0)]
+    );
+    if let Some(value) = symbols.try_get_mut("value", "x") {
+        *value += 10;
+    }
+    assert_eq!(
+        symbols.iter_namespace("value").collect::<Vec<_>>(),
+        [("value", &"x", &13)]
+    );
+    let mut all: Vec<_> = symbols.iter().collect();
+    all.sort_unstable();
+    assert_eq!(
+        all,
+        [
+            ("macro", &"m", &4),
+            ("type", &"x", &1),
+            ("value", &"x", &13)
+        ]
+    );
+    assert_eq!(symbols.remove("value", "x"), Some(13));
+    assert_eq!(symbols.remove("type", "x"), None);
+    assert_eq!(symbols.get("value", "x"), Some(&2));
+    symbols.pop();
+    assert!(!symbols.contains_key("macro", "m"));
+    assert!(symbols.contains_key("type", "x"));
+    assert_eq!(symbols.try_pop(), Err(PopError));
+    symbols.jump(2);
+    assert_eq!(symbols.depth(), 2);
+    assert!(!symbols.is_empty());
+    assert_eq!(symbols.into_inner().get(&("value", "x")), Some(&2));
+}
+
+/// A test of overloading keys, starting from an empty symbol table
+pub fn overload_symbol_table_test<S: OverloadSymbolMap<&'static str, Value = usize>>(
+    symbols: &mut S,
+) {
+    fn all<'a, I: Iterator<Item = (&'a usize, usize)>>(iter: I) -> Vec<(usize, usize)> {
+        iter.map(|(value, depth)| (*value, depth)).collect()
+    }
+    assert_eq!(all(symbols.get_all("f")), []);
+    symbols.insert_overload("f", 1);
+    symbols.insert_overload("f", 2);
+    symbols.insert("g", 3);
+    assert_eq!(symbols.get("f"), Some(&2));
+    assert_eq!(all(symbols.get_all("f")), [(2, 0), (1, 0)]);
+    assert_eq!(all(symbols.get_all("g")), [(3, 0)]);
+    symbols.push();
+    assert_eq!(all(symbols.get_all("f")), [(2, 0), (1, 0)]);
+    symbols.insert_overload("f", 4);
+    symbols.push();
+    symbols.insert_overload("f", 5);
+    symbols.insert_overload("f", 6);
+    assert_eq!(symbols.get_full("f"), Some((&6, 2)));
+    assert_eq!(all(symbols.get_all("f")), [(6, 2), (5, 2)]);
+    assert_eq!(
+        all(symbols.get_all_merged("f")),
+        [(6, 2), (5, 2), (4, 1), (2, 0), (1, 0)]
+    );
+    // Overloads are not yielded by `get_defs`, which only yields the most recent definition at each depth
+    assert_eq!(all(symbols.get_defs("f")), [(6, 2), (4, 1), (2, 0)]);
+    assert_eq!(all(symbols.get_all_merged("h")), []);
+    // `insert` replaces every overload at the current level, but not those at enclosing levels
+    symbols.insert("f", 7);
+    assert_eq!(all(symbols.get_all("f")), [(7, 2)]);
+    assert_eq!(
+        all(symbols.get_all_merged("f")),
+        [(7, 2), (4, 1), (2, 0), (1, 0)]
+    );
+    symbols.insert_overload("f", 8);
+    assert_eq!(all(symbols.get_all("f")), [(8, 2), (7, 2)]);
+    assert_eq!(symbols.remove("f"), Some(8));
+    assert_eq!(all(symbols.get_all("f")), [(4, 1)]);
+    symbols.pop();
+    symbols.pop();
+    assert_eq!(all(symbols.get_all_merged("f")), [(2, 0), (1, 0)]);
+    symbols.insert("f", 9);
+    assert_eq!(all(symbols.get_all_merged("f")), [(9, 0)]);
+}
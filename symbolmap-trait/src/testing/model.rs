@@ -0,0 +1,308 @@
+/*!
+A model-based property test for `SymbolMap` implementations, checking random sequences of operations against a naive
+reference model
+*/
+use super::*;
+use proptest::prelude::*;
+use proptest::test_runner::{Config, TestCaseError, TestRunner};
+use std::collections::HashMap;
+
+/// The number of distinct keys used by generated operations, kept small so that definitions are frequently shadowed
+pub const MODEL_KEYS: u8 = 8;
+
+/// An operation on a symbol table with small integer keys
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Op {
+    /// Push a level onto the symbol table
+    Push,
+    /// Pop a level from the symbol table
+    Pop,
+    /// Insert a definition at the current level
+    Insert(u8, usize),
+    /// Try to insert a definition at the current level, failing if the key is already defined there
+    TryInsert(u8, usize),
+    /// Remove the definition of a key at the current level
+    Remove(u8),
+    /// Look up a key
+    Get(u8),
+    /// Try to overwrite the innermost definition of a key
+    GetMut(u8, usize),
+}
+
+/// A strategy generating a single operation
+pub fn op() -> impl Strategy<Value = Op> {
+    let key = || 0..MODEL_KEYS;
+    prop_oneof![
+        2 => Just(Op::Push),
+        2 => Just(Op::Pop),
+        4 => (key(), any::<usize>()).prop_map(|(key, value)| Op::Insert(key, value)),
+        1 => (key(), any::<usize>()).prop_map(|(key, value)| Op::TryInsert(key, value)),
+        1 => key().prop_map(Op::Remove),
+        2 => key().prop_map(Op::Get),
+        1 => (key(), any::<usize>()).prop_map(|(key, value)| Op::GetMut(key, value)),
+    ]
+}
+
+/// A strategy generating a sequence of operations
+pub fn ops() -> impl Strategy<Value = Vec<Op>> {
+    proptest::collection::vec(op(), 0..128)
+}
+
+/// A naive reference model of a symbol table, consisting of a stack of hash maps, one for each level
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Model {
+    /// The definitions made at each level, from the base level up
+    levels: Vec<HashMap<u8, usize>>,
+    /// The depth of the base level
+    base: usize,
+}
+
+impl Default for Model {
+    #[inline]
+    fn default() -> Model {
+        Model::with_base(0)
+    }
+}
+
+impl Model {
+    /// Create a new, empty model
+    #[inline]
+    pub fn new() -> Model {
+        Self::default()
+    }
+    /// Create a new, empty model whose base level is at a given depth, for symbol tables with levels beneath the ones
+    /// they let users push and pop
+    #[inline]
+    pub fn with_base(base: usize) -> Model {
+        Model {
+            levels: vec![HashMap::new()],
+            base,
+        }
+    }
+    /// Get the current depth of the model
+    #[inline]
+    pub fn depth(&self) -> usize {
+        self.base + self.levels.len() - 1
+    }
+    /// Get the definitions of a key, from most to least recent, along with their depths
+    pub fn defs(&self, key: u8) -> Vec<(usize, usize)> {
+        self.levels
+            .iter()
+            .enumerate()
+            .rev()
+            .filter_map(|(ix, level)| level.get(&key).map(|value| (*value, self.base + ix)))
+            .collect()
+    }
+    /// Get the innermost definition of a key, along with its depth
+    #[inline]
+    pub fn get_full(&self, key: u8) -> Option<(usize, usize)> {
+        self.defs(key).first().copied()
+    }
+    /// Get the bindings visible in the model
+    pub fn visible(&self) -> HashMap<u8, usize> {
+        let mut visible = HashMap::new();
+        for level in &self.levels {
+            visible.extend(level.iter().map(|(key, value)| (*key, *value)));
+        }
+        visible
+    }
+    /// Get the bindings defined at the current level of the model
+    #[inline]
+    pub fn top(&self) -> &HashMap<u8, usize> {
+        self.levels
+            .last()
+            .expect("the model always has a base level")
+    }
+    /// Get the bindings defined at the current level of the model, mutably
+    #[inline]
+    fn top_mut(&mut self) -> &mut HashMap<u8, usize> {
+        self.levels
+            .last_mut()
+            .expect("the model always has a base level")
+    }
+    /// Push a level onto the model
+    #[inline]
+    pub fn push(&mut self) {
+        self.levels.push(HashMap::new())
+    }
+    /// Pop a level from the model, doing nothing if it is at its base level
+    #[inline]
+    pub fn pop(&mut self) {
+        if self.levels.len() > 1 {
+            self.levels.pop();
+        }
+    }
+    /// Jump to a given depth, pushing or popping levels as necessary, but never below the base level
+    #[inline]
+    pub fn jump(&mut self, depth: usize) {
+        self.levels
+            .resize_with(depth.saturating_sub(self.base) + 1, HashMap::new)
+    }
+    /// Insert a definition at the current level, replacing any definition of the key made there
+    #[inline]
+    pub fn insert(&mut self, key: u8, value: usize) {
+        self.top_mut().insert(key, value);
+    }
+    /// Insert a definition at the current level, failing with the existing definition if the key is already defined
+    /// there
+    #[inline]
+    pub fn try_insert(&mut self, key: u8, value: usize) -> Result<(), usize> {
+        match self.top().get(&key) {
+            Some(existing) => Err(*existing),
+            None => {
+                self.insert(key, value);
+                Ok(())
+            }
+        }
+    }
+    /// Remove the definition of a key at the current level, returning it
+    #[inline]
+    pub fn remove(&mut self, key: u8) -> Option<usize> {
+        self.top_mut().remove(&key)
+    }
+    /// Get a mutable reference to the innermost definition of a key
+    #[inline]
+    pub fn get_mut(&mut self, key: u8) -> Option<&mut usize> {
+        self.levels
+            .iter_mut()
+            .rev()
+            .find_map(|level| level.get_mut(&key))
+    }
+}
+
+/// Apply a sequence of operations to a symbol table and a reference model, checking that they agree after each one
+pub fn check_model<S: SymbolMap<u8, Value = usize>>(
+    symbols: &mut S,
+    ops: &[Op],
+) -> Result<(), TestCaseError> {
+    check_model_from(symbols, Model::new(), ops)
+}
+
+/// Apply a sequence of operations to a symbol table and a given reference model, checking that they agree after each
+/// one
+pub fn check_model_from<S: SymbolMap<u8, Value = usize>>(
+    symbols: &mut S,
+    mut model: Model,
+    ops: &[Op],
+) -> Result<(), TestCaseError> {
+    check_state(symbols, &model)?;
+    for op in ops {
+        match *op {
+            Op::Push => {
+                symbols.push();
+                model.push();
+            }
+            Op::Pop => {
+                symbols.pop();
+                model.pop();
+            }
+            Op::Insert(key, value) => {
+                symbols.insert(key, value);
+                model.insert(key, value);
+            }
+            Op::TryInsert(key, value) => {
+                let result = symbols
+                    .try_insert(key, value)
+                    .map_err(|err| (err.value, *err.existing));
+                let expected = model
+                    .try_insert(key, value)
+                    .map_err(|existing| (value, existing));
+                prop_assert_eq!(result, expected, "{:?}", op);
+            }
+            Op::Remove(key) => {
+                let removed = symbols.remove(&key);
+                prop_assert_eq!(removed, model.remove(key), "{:?}", op);
+            }
+            Op::Get(key) => {
+                prop_assert_eq!(
+                    symbols.get(&key).copied(),
+                    model.get_full(key).map(|(value, _)| value),
+                    "{:?}",
+                    op
+                );
+            }
+            Op::GetMut(key, value) => {
+                // `try_get_mut` is permitted to fail, but must return the innermost definition if it succeeds
+                if let Some(slot) = symbols.try_get_mut(&key) {
+                    let expected = model.get_mut(key).ok_or_else(|| {
+                        TestCaseError::fail(format!("{:?}: found an undefined key", op))
+                    })?;
+                    prop_assert_eq!(*slot, *expected, "{:?}", op);
+                    *slot = value;
+                    *expected = value;
+                }
+            }
+        }
+        check_state(symbols, &model)?;
+    }
+    Ok(())
+}
+
+/// Check that the observable state of a symbol table agrees with a reference model
+fn check_state<S: SymbolMap<u8, Value = usize>>(
+    symbols: &S,
+    model: &Model,
+) -> Result<(), TestCaseError> {
+    prop_assert_eq!(symbols.depth(), model.depth());
+    prop_assert_eq!(symbols.is_empty(), model.visible().is_empty());
+    for key in 0..MODEL_KEYS {
+        let defs = model.defs(key);
+        prop_assert_eq!(symbols.contains_key(&key), !defs.is_empty());
+        prop_assert_eq!(
+            symbols.get_full(&key).map(|(value, depth)| (*value, depth)),
+            defs.first().copied()
+        );
+        let found = symbols
+            .get_defs(&key)
+            .map(|(value, depth)| (*value, depth))
+            .collect::<Vec<_>>();
+        prop_assert_eq!(found, defs);
+    }
+    let mut visible = HashMap::new();
+    for (key, value) in symbols.iter() {
+        prop_assert!(
+            visible.insert(*key, *value).is_none(),
+            "binding for {} yielded twice",
+            key
+        );
+    }
+    prop_assert_eq!(visible, model.visible());
+    let mut top = HashMap::new();
+    for (key, value) in symbols.iter_top() {
+        prop_assert!(
+            top.insert(*key, *value).is_none(),
+            "top-level binding for {} yielded twice",
+            key
+        );
+    }
+    prop_assert_eq!(&top, model.top());
+    Ok(())
+}
+
+/// Run the model checker against symbol tables created by `new`, panicking with a minimal failing sequence of
+/// operations if the symbol table and the reference model ever disagree
+pub fn model_symbol_table_test<S, F>(new: F)
+where
+    S: SymbolMap<u8, Value = usize>,
+    F: Fn() -> S,
+{
+    model_symbol_table_test_with_base(new, 0)
+}
+
+/// Run the model checker against symbol tables created by `new` whose base level is at depth `base`, such as tables
+/// with a prelude beneath them
+pub fn model_symbol_table_test_with_base<S, F>(new: F, base: usize)
+where
+    S: SymbolMap<u8, Value = usize>,
+    F: Fn() -> S,
+{
+    let mut runner = TestRunner::new(Config {
+        failure_persistence: None,
+        ..Config::default()
+    });
+    if let Err(err) = runner.run(&ops(), |ops| {
+        check_model_from(&mut new(), Model::with_base(base), &ops)
+    }) {
+        panic!("symbol table disagrees with the reference model: {}", err)
+    }
+}
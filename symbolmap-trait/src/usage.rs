@@ -0,0 +1,277 @@
+/*!
+Tracking of the uses of each binding in a symbol table, for diagnosing unused and shadowed bindings
+*/
+use super::*;
+use std::cell::Cell;
+use std::collections::HashMap;
+
+/// A diagnostic about the uses of a binding in a `Tracked` symbol table
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum UsageDiagnostic<K> {
+    /// A binding was discarded, by popping its level or removing it, without ever being used
+    Unused {
+        /// The key of the binding
+        key: K,
+        /// The depth at which the binding was defined
+        depth: usize,
+    },
+    /// A binding was shadowed by a new definition of its key
+    Shadowed {
+        /// The key of the bindings
+        key: K,
+        /// The depth of the new definition
+        depth: usize,
+        /// The depth of the shadowed binding, which is equal to `depth` if it was redefined at the same level
+        shadowed_depth: usize,
+        /// The number of times the shadowed binding had been used.
+        ///
+        /// A binding redefined at the same level can never be used again, so if this is 0 it is never used. A binding
+        /// shadowed at an outer level may still be used once the new definition's level is popped, and is reported as
+        /// `Unused` if it never is.
+        uses: usize,
+    },
+}
+
+/**
+A symbol table which counts the uses of each binding inserted into it, reporting bindings which are shadowed or
+discarded without being used.
+
+Lookups via `get`, `get_full` and `try_get_mut` count as uses of the innermost definition of a key, whereas
+`contains_key`, `get_defs` and iteration do not. Diagnostics are accumulated as the table is modified, and may be taken
+via [`Tracked::take_diagnostics`]. Only bindings inserted through the wrapper are tracked.
+*/
+#[derive(Debug, Clone)]
+pub struct Tracked<K, T> {
+    /// The underlying symbol table
+    table: T,
+    /// The number of uses of each binding defined at each level, from the base level up
+    levels: Vec<HashMap<K, Cell<usize>>>,
+    /// The diagnostics reported so far
+    diagnostics: Vec<UsageDiagnostic<K>>,
+}
+
+impl<K: Hash + Eq + Clone, T: SymbolMap<K>> Tracked<K, T> {
+    /// Track the uses of the bindings inserted into a symbol table from now on
+    #[inline]
+    pub fn new(table: T) -> Tracked<K, T> {
+        let levels = (0..=table.depth()).map(|_| HashMap::new()).collect();
+        Tracked {
+            table,
+            levels,
+            diagnostics: Vec::new(),
+        }
+    }
+    /// Get the underlying symbol table
+    #[inline]
+    pub fn table(&self) -> &T {
+        &self.table
+    }
+    /// Stop tracking uses, returning the underlying symbol table
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.table
+    }
+    /// Get the diagnostics reported so far, in the order they were reported
+    #[inline]
+    pub fn diagnostics(&self) -> &[UsageDiagnostic<K>] {
+        &self.diagnostics
+    }
+    /// Take the diagnostics reported so far, in the order they were reported
+    #[inline]
+    pub fn take_diagnostics(&mut self) -> Vec<UsageDiagnostic<K>> {
+        std::mem::take(&mut self.diagnostics)
+    }
+    /// Get the number of times the innermost definition of a key has been used, if it is tracked
+    pub fn uses<Q>(&self, key: &Q) -> Option<usize>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        let (_, depth) = self.table.get_full(key)?;
+        self.levels[depth].get(key).map(Cell::get)
+    }
+    /// Get the tracked bindings which have not been used so far, along with their depths, in arbitrary order
+    ///
+    /// This includes bindings which are shadowed, and is useful to diagnose the bindings of the base level, which are
+    /// never popped.
+    pub fn unused(&self) -> impl Iterator<Item = (&K, usize)> {
+        self.levels.iter().enumerate().flat_map(|(depth, level)| {
+            level
+                .iter()
+                .filter(|(_, uses)| uses.get() == 0)
+                .map(move |(key, _)| (key, depth))
+        })
+    }
+    /// Count a use of the definition of a key at a given depth
+    #[inline]
+    fn use_at<Q>(&self, key: &Q, depth: usize)
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        if let Some(uses) = self.levels[depth].get(key) {
+            uses.set(uses.get() + 1)
+        }
+    }
+    /// Track a new definition of a key at the current level, reporting any binding it shadows
+    fn define(&mut self, key: &K) {
+        let depth = self.table.depth();
+        let shadowed = self.table.get_full(key).map(|(_, depth)| depth);
+        let redefined = self.levels[depth].insert(key.clone(), Cell::new(0));
+        if let Some(shadowed_depth) = shadowed {
+            let uses = if shadowed_depth == depth {
+                redefined.map(|uses| uses.get())
+            } else {
+                self.levels[shadowed_depth].get(key).map(Cell::get)
+            };
+            if let Some(uses) = uses {
+                self.diagnostics.push(UsageDiagnostic::Shadowed {
+                    key: key.clone(),
+                    depth,
+                    shadowed_depth,
+                    uses,
+                })
+            }
+        }
+    }
+    /// Stop tracking the levels above a given depth, reporting their unused bindings
+    fn discard_levels(&mut self, depth: usize) {
+        while self.levels.len() > depth + 1 {
+            let level_depth = self.levels.len() - 1;
+            let level = self
+                .levels
+                .pop()
+                .expect("levels above the target depth exist");
+            self.diagnostics
+                .extend(
+                    level
+                        .into_iter()
+                        .filter(|(_, uses)| uses.get() == 0)
+                        .map(|(key, _)| UsageDiagnostic::Unused {
+                            key,
+                            depth: level_depth,
+                        }),
+                )
+        }
+    }
+}
+
+impl<K: Hash + Eq + Clone, T: SymbolMap<K>> SymbolMap<K> for Tracked<K, T> {
+    type Value = T::Value;
+    type Defs<'a>
+        = T::Defs<'a>
+    where
+        Self: 'a;
+    type Iter<'a>
+        = T::Iter<'a>
+    where
+        Self: 'a;
+    type IterTop<'a>
+        = T::IterTop<'a>
+    where
+        Self: 'a;
+    #[inline]
+    fn insert(&mut self, key: K, value: T::Value) {
+        self.define(&key);
+        self.table.insert(key, value)
+    }
+    #[inline]
+    fn try_insert(&mut self, key: K, value: T::Value) -> Result<(), AlreadyDefined<'_, T::Value>> {
+        if self.table.get_full(&key).map(|(_, depth)| depth) != Some(self.table.depth()) {
+            self.define(&key);
+        }
+        self.table.try_insert(key, value)
+    }
+    #[inline]
+    fn get<Q>(&self, key: &Q) -> Option<&T::Value>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        self.get_full(key).map(|(value, _)| value)
+    }
+    #[inline]
+    fn get_full<Q>(&self, key: &Q) -> Option<(&T::Value, usize)>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        let (value, depth) = self.table.get_full(key)?;
+        self.use_at(key, depth);
+        Some((value, depth))
+    }
+    #[inline]
+    fn get_defs<Q>(&self, key: &Q) -> T::Defs<'_>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        self.table.get_defs(key)
+    }
+    fn remove<Q>(&mut self, key: &Q) -> Option<T::Value>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        let depth = self.table.depth();
+        let removed = self.table.remove(key)?;
+        if let Some((key, uses)) = self.levels[depth].remove_entry(key) {
+            if uses.get() == 0 {
+                self.diagnostics
+                    .push(UsageDiagnostic::Unused { key, depth })
+            }
+        }
+        Some(removed)
+    }
+    #[inline]
+    fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        self.table.contains_key(key)
+    }
+    #[inline]
+    fn iter(&self) -> T::Iter<'_> {
+        self.table.iter()
+    }
+    #[inline]
+    fn iter_top(&self) -> T::IterTop<'_> {
+        self.table.iter_top()
+    }
+    #[inline]
+    fn is_empty(&self) -> bool {
+        self.table.is_empty()
+    }
+    #[inline]
+    fn try_get_mut<Q>(&mut self, key: &Q) -> Option<&mut T::Value>
+    where
+        Q: ?Sized + Hash + Eq,
+        K: Borrow<Q>,
+    {
+        let (_, depth) = self.table.get_full(key)?;
+        self.use_at(key, depth);
+        self.table.try_get_mut(key)
+    }
+    #[inline]
+    fn push(&mut self) {
+        self.table.push();
+        self.levels.push(HashMap::new());
+    }
+    #[inline]
+    fn pop(&mut self) {
+        if self.table.depth() != 0 {
+            self.jump(self.table.depth() - 1)
+        }
+    }
+    #[inline]
+    fn jump(&mut self, depth: usize) {
+        self.discard_levels(depth);
+        self.table.jump(depth);
+        self.levels.resize_with(depth + 1, HashMap::new);
+    }
+    #[inline]
+    fn depth(&self) -> usize {
+        self.table.depth()
+    }
+}